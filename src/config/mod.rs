@@ -2,4 +2,4 @@ mod parser;
 mod types;
 
 pub use parser::{parse_config_file, parse_config_string};
-pub use types::{RouteConfig, ServerConfig, VHost};
+pub use types::{match_ext_rule, AuthConfig, AuthScheme, AutoindexFormat, Expires, ExtRule, JwtConfig, RouteConfig, SameSite, ServerConfig, SessionBackend, SessionConfig, SessionMode, UploadConflict, VHost};
@@ -1,7 +1,14 @@
-use super::types::{RouteConfig, ServerConfig, VHost};
+use super::types::{AuthConfig, AuthScheme, AutoindexFormat, Expires, ExtRule, RouteConfig, SameSite, ServerConfig, SessionBackend, SessionConfig, SessionMode, UploadConflict, VHost};
+use crate::http::{registered_reason, MimeTypes};
 use std::fs;
 use std::io;
 
+/// Responses smaller than this aren't worth compressing by default
+const DEFAULT_GZIP_MIN_LENGTH: usize = 1024;
+
+/// Shorter `session_secret` keys are accepted with a warning
+const MIN_SECRET_LEN: usize = 32;
+
 pub fn parse_config_file(path: &str) -> io::Result<ServerConfig> {
     let content = fs::read_to_string(path)?;
     parse_config_string(&content)
@@ -11,20 +18,17 @@ pub fn parse_config_string(content: &str) -> io::Result<ServerConfig> {
     let mut listen_addresses = Vec::new();
     let mut client_body_size_limit = 10 * 1024 * 1024;
     let mut error_path = String::new();
+    let mut mime_types = MimeTypes::builtin();
+    let mut default_type = "application/octet-stream".to_string();
+    let mut session = SessionConfig::default();
     let mut routes = Vec::new();
     let mut vhosts = Vec::new();
     
     let mut context = ParsingContext::TopLevel;
     let mut in_vhost = false;
     
-    let mut current_route_path = String::new();
-    let mut current_route_methods = Vec::new();
-    let mut current_route_root = String::new();
-    let mut current_default_file = String::new();
-    let mut current_route_autoindex = false;
+    let mut current_route = RouteConfig::default();
     let mut current_vhost: Option<VHost> = None;
-    let mut current_cgi: Option<String> = None;
-    let mut current_redirect: Option<String> = None;
 
     for (line_num, line) in content.lines().enumerate() {
         let line = line.trim();
@@ -65,13 +69,16 @@ pub fn parse_config_string(content: &str) -> io::Result<ServerConfig> {
                         name: parts[1].to_string(),
                         error_path: error_path.clone(),
                         routes: Vec::new(),
+                        sessions: None,
+                        auth: AuthConfig::default(),
                     });
                     in_vhost = true;
                 }
                 else if line.starts_with("route") && line.ends_with('{') {
                     let parts: Vec<&str> = line.split_whitespace().collect();
                     if parts.len() >= 3 && parts[2] == "{" {
-                        current_route_path = parts[1].to_string();
+                        current_route.path = parts[1].to_string();
+                        current_route.gzip_min_length = DEFAULT_GZIP_MIN_LENGTH;
                         context = ParsingContext::InsideRoute;
                     }
                 }
@@ -91,54 +98,81 @@ pub fn parse_config_string(content: &str) -> io::Result<ServerConfig> {
                     }
                 }
                 else if line.starts_with("client_body_size_limit") || line.starts_with("client_max_body_size") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        if let Ok(size) = value.trim().parse::<usize>() {
-                            // Minimum 1KB, Maximum 100MB
-                            const MIN_BODY_SIZE: usize = 1024;           // 1KB
-                            const MAX_BODY_SIZE: usize = 100 * 1024 * 1024; // 100MB
-                            
-                            if size < MIN_BODY_SIZE {
-                                eprintln!("[config] WARNING: client_body_size_limit too small ({}), using minimum: {}", size, MIN_BODY_SIZE);
-                                client_body_size_limit = MIN_BODY_SIZE;
-                            } else if size > MAX_BODY_SIZE {
-                                eprintln!("[config] WARNING: client_body_size_limit too large ({}), using maximum: {}", size, MAX_BODY_SIZE);
-                                client_body_size_limit = MAX_BODY_SIZE;
-                            } else {
-                                client_body_size_limit = size;
-                            }
+                    if let Some(value) = line.split('=').nth(1)
+                        && let Ok(size) = value.trim().parse::<usize>()
+                    {
+                        // Minimum 1KB, Maximum 100MB
+                        const MIN_BODY_SIZE: usize = 1024;           // 1KB
+                        const MAX_BODY_SIZE: usize = 100 * 1024 * 1024; // 100MB
+                        
+                        if size < MIN_BODY_SIZE {
+                            eprintln!("[config] WARNING: client_body_size_limit too small ({}), using minimum: {}", size, MIN_BODY_SIZE);
+                            client_body_size_limit = MIN_BODY_SIZE;
+                        } else if size > MAX_BODY_SIZE {
+                            eprintln!("[config] WARNING: client_body_size_limit too large ({}), using maximum: {}", size, MAX_BODY_SIZE);
+                            client_body_size_limit = MAX_BODY_SIZE;
+                        } else {
+                            client_body_size_limit = size;
                         }
                     }
                 }
-                else if line.starts_with("error_path") || line.starts_with("error_dir") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        error_path = value.trim().to_string();
-                        if let Some(vh) = &mut current_vhost {
-                            vh.error_path = error_path.clone();
-                        }
+                else if (line.starts_with("error_path") || line.starts_with("error_dir"))
+                    && let Some(value) = line.split('=').nth(1)
+                {
+                    error_path = value.trim().to_string();
+                    if let Some(vh) = &mut current_vhost {
+                        vh.error_path = error_path.clone();
+                    }
+                }
+                else if line.starts_with("mime_types")
+                    && let Some(value) = line.split('=').nth(1)
+                {
+                    match mime_types.load_file(value.trim()) {
+                        Ok(count) => println!("[config] loaded {} MIME types from {}", count, value.trim()),
+                        Err(e) => eprintln!("[config] WARNING: cannot read mime_types file {}: {}", value.trim(), e),
+                    }
+                }
+                else if line.starts_with("default_type")
+                    && let Some(value) = line.split('=').nth(1)
+                {
+                    default_type = value.trim().to_string();
+                }
+                else if line.starts_with("sessions")
+                    && let Some(value) = line.split('=').nth(1)
+                {
+                    match parse_session_mode(value.trim()) {
+                        Some(mode) => match &mut current_vhost {
+                            Some(vh) => vh.sessions = Some(mode),
+                            None => session.mode = mode,
+                        },
+                        None => eprintln!("[config] WARNING: invalid sessions (off, lazy or always): {}", value.trim()),
+                    }
+                }
+                else if line.starts_with("auth_") {
+                    // A lost auth line would leave the vhost open, so these are errors
+                    let (directive, value) = split_auth_line(line).ok_or_else(|| config_error(line_num, &format!("{} needs a value", line)))?;
+                    match &mut current_vhost {
+                        Some(vh) => parse_auth_directive(&mut vh.auth, directive, value).map_err(|e| config_error(line_num, &e))?,
+                        None => return Err(config_error(line_num, &format!("{} belongs in a route or vhost block", directive))),
                     }
                 }
+                else if line.starts_with("session_")
+                    && let Some((key, value)) = line.split_once('=')
+                {
+                    parse_session_directive(&mut session, key.trim(), value.trim());
+                }
             }
             
             ParsingContext::InsideRoute => {
                 if line == "}" {
-                    if !current_route_root.is_empty() || current_redirect.is_some() {
-                        let route = RouteConfig {
-                            path: current_route_path.clone(),
-                            methods: if current_route_methods.is_empty() {
-                                vec!["GET".to_string()]
-                            } else {
-                                current_route_methods.clone()
-                            },
-                            root: current_route_root.clone(),
-                            default_file: if current_default_file.is_empty() {
-                                Some("index.html".to_string())
-                            } else {
-                                Some(current_default_file.clone())
-                            },
-                            autoindex: current_route_autoindex,
-                            cgi: current_cgi.clone(),
-                            redirect: current_redirect.clone(),
-                        };
+                    let mut route = std::mem::take(&mut current_route);
+                    if !route.root.is_empty() || route.redirect.is_some() {
+                        if route.methods.is_empty() {
+                            route.methods = vec!["GET".to_string()];
+                        }
+                        if route.default_file.is_none() {
+                            route.default_file = Some("index.html".to_string());
+                        }
                         
                         if let Some(vh) = &mut current_vhost {
                             vh.routes.push(route);
@@ -147,50 +181,23 @@ pub fn parse_config_string(content: &str) -> io::Result<ServerConfig> {
                         }
                     }
                     
-                    // Reset
-                    current_route_path.clear();
-                    current_route_methods.clear();
-                    current_route_root.clear();
-                    current_default_file.clear();
-                    current_route_autoindex = false;
-                    current_cgi = None;
-                    current_redirect = None;
                     context = ParsingContext::TopLevel;
                 }
-                else if line.starts_with("methods") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        current_route_methods = value
-                            .split(',')
-                            .map(|m| m.trim().to_uppercase())
-                            .filter(|m| !m.is_empty())
-                            .collect();
-                    }
-                }
-                else if line.starts_with("default_file") || line.starts_with("default") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        current_default_file = value.trim().to_string();
-                    }
-                }
-                else if line.starts_with("root") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        current_route_root = value.trim().to_string();
-                    }
-                }
-                else if line.starts_with("autoindex") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        let val = value.trim().to_lowercase();
-                        current_route_autoindex = val == "on" || val == "true" || val == "yes";
-                    }
-                }
-                else if line.starts_with("cgi") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        current_cgi = Some(value.trim().to_string());
-                    }
+                else if line.starts_with("auth_") {
+                    let (directive, value) = split_auth_line(line).ok_or_else(|| config_error(line_num, &format!("{} needs a value", line)))?;
+                    parse_auth_directive(&mut current_route.auth, directive, value).map_err(|e| config_error(line_num, &e))?;
                 }
-                else if line.starts_with("redirect") {
-                    if let Some(value) = line.split('=').nth(1) {
-                        current_redirect = Some(value.trim().to_string());
-                    }
+                else if let Some((key, value)) = line.split_once('=') {
+                    // The key may carry extension qualifiers: `cache_control .js .css = ...`
+                    let mut key_parts = key.split_whitespace();
+                    let directive = key_parts.next().unwrap_or("");
+                    let extensions: Vec<String> = key_parts
+                        .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                        .filter(|e| !e.is_empty())
+                        .collect();
+                    let value = value.trim();
+                    
+                    parse_route_directive(&mut current_route, directive, extensions, value).map_err(|e| config_error(line_num, &e))?;
                 }
             }
         }
@@ -204,17 +211,330 @@ pub fn parse_config_string(content: &str) -> io::Result<ServerConfig> {
         listen_addresses.push("127.0.0.1:8080".to_string());
     }
     
+    if session.store == SessionBackend::Cookie && session.secrets.is_empty() {
+        eprintln!("[config] WARNING: session_store = cookie needs session_secret; keeping sessions in memory");
+        session.store = SessionBackend::Memory;
+    }
+    
     Ok(ServerConfig {
         listen_addresses,
         client_body_size_limit,
         routes,
         error_path,
         vhosts,
+        mime_types,
+        default_type,
+        session,
     })
 }
 
+/// A config file problem, reported with its 1-based line number
+fn config_error(line_num: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_num + 1, message))
+}
+
 #[derive(PartialEq)]
 enum ParsingContext {
     TopLevel,
     InsideRoute,
 }
+
+/// Apply a single `key = value` line found inside a `route { }` block.
+/// Most mistakes only warn; the ones that would change responses are errors.
+fn parse_route_directive(route: &mut RouteConfig, directive: &str, extensions: Vec<String>, value: &str) -> Result<(), String> {
+    match directive {
+        "methods" => {
+            route.methods = value
+                .split(',')
+                .map(|m| m.trim().to_uppercase())
+                .filter(|m| !m.is_empty())
+                .collect();
+        }
+        "default_file" | "default" => {
+            if !value.is_empty() {
+                route.default_file = Some(value.to_string());
+            }
+        }
+        "root" => route.root = value.to_string(),
+        "autoindex" => route.autoindex = parse_bool(value),
+        "autoindex_archive" => route.autoindex_archive = parse_bool(value),
+        "autoindex_format" => match value.to_lowercase().as_str() {
+            "html" => route.autoindex_format = AutoindexFormat::Html,
+            "json" => route.autoindex_format = AutoindexFormat::Json,
+            "text" | "plain" => route.autoindex_format = AutoindexFormat::Text,
+            _ => eprintln!("[config] WARNING: invalid autoindex_format: {}", value),
+        },
+        "cgi" => route.cgi = Some(value.to_string()),
+        "redirect" => route.redirect = Some(value.to_string()),
+        "cache_control" => {
+            route.cache_control.push(ExtRule { extensions, value: value.to_string() });
+        }
+        "expires" => match parse_expires(value) {
+            Some(expires) => route.expires.push(ExtRule { extensions, value: expires }),
+            None => eprintln!("[config] WARNING: invalid expires value: {}", value),
+        },
+        "mime_type" => {
+            if extensions.is_empty() {
+                eprintln!("[config] WARNING: mime_type needs at least one extension: mime_type .ext = type/subtype");
+            } else {
+                route.mime_types.push(ExtRule { extensions, value: value.to_string() });
+            }
+        }
+        "default_type" => route.default_type = Some(value.to_string()),
+        "gzip" => route.gzip = parse_bool(value),
+        "try_files" => {
+            route.try_files = value.split_whitespace().map(|s| s.to_string()).collect();
+            // A `=code` fallback must be a registered error or success status
+            if let Some(code) = route.try_files.last().and_then(|last| last.strip_prefix('=')) {
+                match code.parse::<u16>() {
+                    Ok(status) if (200..600).contains(&status) && registered_reason(status).is_some() => {}
+                    _ => return Err(format!("try_files: unknown status code ={}", code)),
+                }
+            }
+        }
+        "gzip_static" => route.gzip_static = parse_bool(value),
+        "gzip_min_length" => match parse_size(value) {
+            Some(size) => route.gzip_min_length = size,
+            None => eprintln!("[config] WARNING: invalid gzip_min_length: {}", value),
+        },
+        "tus" => route.tus = parse_bool(value),
+        "webdav" => route.webdav = parse_bool(value),
+        "sessions" => match parse_session_mode(value) {
+            Some(mode) => route.sessions = Some(mode),
+            None => eprintln!("[config] WARNING: invalid sessions (off, lazy or always): {}", value),
+        },
+        "create_dirs" => route.create_dirs = parse_bool(value),
+        "delete_recursive" => route.delete_recursive = parse_bool(value),
+        "trash" => route.trash = parse_bool(value),
+        "trash_retention" => match parse_duration_secs(value) {
+            Some(secs) => route.trash_retention = Some(secs),
+            None => eprintln!("[config] WARNING: invalid trash_retention: {}", value),
+        },
+        "upload_conflict" => match value.to_lowercase().as_str() {
+            "overwrite" => route.upload_conflict = UploadConflict::Overwrite,
+            "rename" => route.upload_conflict = UploadConflict::Rename,
+            "reject" => route.upload_conflict = UploadConflict::Reject,
+            _ => eprintln!("[config] WARNING: invalid upload_conflict: {}", value),
+        },
+        "upload_allow_ext" => route.upload_allow_ext = parse_extension_list(value),
+        "upload_deny_ext" => route.upload_deny_ext = parse_extension_list(value),
+        "upload_max_files" => match value.parse() {
+            Ok(max) => route.upload_max_files = Some(max),
+            Err(_) => eprintln!("[config] WARNING: invalid upload_max_files: {}", value),
+        },
+        "upload_dir_quota" => match parse_size(value) {
+            Some(quota) => route.upload_dir_quota = Some(quota as u64),
+            None => eprintln!("[config] WARNING: invalid upload_dir_quota: {}", value),
+        },
+        "upload_sniff" => route.upload_sniff = parse_bool(value),
+        _ => eprintln!("[config] WARNING: unknown route directive: {}", directive),
+    }
+    Ok(())
+}
+
+/// Apply a top-level `session_*` line
+fn parse_session_directive(session: &mut SessionConfig, directive: &str, value: &str) {
+    match directive {
+        "session_cookie" => {
+            // A cookie name is an HTTP token
+            if !value.is_empty() && value.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)) {
+                session.cookie_name = value.to_string();
+            } else {
+                eprintln!("[config] WARNING: invalid session_cookie: {}", value);
+            }
+        }
+        "session_secure" => session.secure = parse_bool(value),
+        "session_samesite" => match value.to_lowercase().as_str() {
+            "strict" => session.same_site = SameSite::Strict,
+            "lax" => session.same_site = SameSite::Lax,
+            "none" => session.same_site = SameSite::None,
+            _ => eprintln!("[config] WARNING: invalid session_samesite: {}", value),
+        },
+        "session_max_age" => match parse_duration_secs(value) {
+            Some(secs) => session.max_age = Some(secs),
+            None => eprintln!("[config] WARNING: invalid session_max_age: {}", value),
+        },
+        "session_timeout" => match parse_duration_secs(value) {
+            Some(secs) if secs > 0 => session.idle_timeout = secs,
+            _ => eprintln!("[config] WARNING: invalid session_timeout: {}", value),
+        },
+        "session_store" => match value.split_once(':') {
+            Some(("file", dir)) if !dir.trim().is_empty() => session.store = SessionBackend::File(dir.trim().to_string()),
+            _ if value == "memory" => session.store = SessionBackend::Memory,
+            _ if value == "cookie" => session.store = SessionBackend::Cookie,
+            _ => eprintln!("[config] WARNING: invalid session_store (memory, file:<dir> or cookie): {}", value),
+        },
+        "session_secret" => {
+            session.secrets = value
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect();
+            if session.secrets.iter().any(|key| key.len() < MIN_SECRET_LEN) {
+                eprintln!("[config] WARNING: session_secret keys should be at least {} characters", MIN_SECRET_LEN);
+            }
+        }
+        _ => eprintln!("[config] WARNING: unknown session directive: {}", directive),
+    }
+}
+
+/// `jpg, .png gif` -> ["jpg", "png", "gif"]
+fn parse_extension_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// `a, b, c` -> ["a", "b", "c"]
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// `auth_basic = "Staff"`, or without the `=` as in nginx: `auth_basic "Staff"`
+fn split_auth_line(line: &str) -> Option<(&str, &str)> {
+    let end = line.find(|c: char| c == '=' || c.is_whitespace())?;
+    let (directive, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let value = rest.strip_prefix('=').unwrap_or(rest).trim();
+    (!value.is_empty()).then_some((directive, value))
+}
+
+/// Apply an `auth_*` line of a route or vhost. Mistakes are errors rather
+/// than warnings, since skipping the line would leave the route open.
+fn parse_auth_directive(auth: &mut AuthConfig, directive: &str, value: &str) -> Result<(), String> {
+    // The realm may be quoted: auth_basic = "Staff only"
+    let realm = value.trim_matches('"').to_string();
+    match directive {
+        "auth_user_file" => auth.user_file = Some(value.to_string()),
+        "auth_jwt_secret" => {
+            if value.len() < MIN_SECRET_LEN {
+                eprintln!("[config] WARNING: auth_jwt_secret should be at least {} characters", MIN_SECRET_LEN);
+            }
+            auth.jwt.secret = Some(value.to_string());
+        }
+        "auth_jwt_key_file" => auth.jwt.key_file = Some(value.to_string()),
+        "auth_jwt_cookie" => auth.jwt.cookie = Some(value.to_string()),
+        "auth_jwt_audience" => auth.jwt.audience = Some(parse_list(value)),
+        "auth_jwt_claims" => auth.jwt.claims = Some(parse_list(value)),
+        "auth_basic" | "auth_digest" | "auth_jwt" if value.eq_ignore_ascii_case("off") => auth.scheme = Some(AuthScheme::Off),
+        "auth_basic" | "auth_digest" | "auth_jwt" if realm.is_empty() || realm.contains('"') || realm.contains('\\') => {
+            return Err(format!("invalid {} realm: {}", directive, value));
+        }
+        "auth_basic" => auth.scheme = Some(AuthScheme::Basic(realm)),
+        "auth_digest" => auth.scheme = Some(AuthScheme::Digest(realm)),
+        "auth_jwt" => auth.scheme = Some(AuthScheme::Jwt(realm)),
+        _ => return Err(format!("unknown auth directive: {}", directive)),
+    }
+    Ok(())
+}
+
+/// `off`, `lazy` or `always`
+fn parse_session_mode(value: &str) -> Option<SessionMode> {
+    match value.to_lowercase().as_str() {
+        "off" => Some(SessionMode::Off),
+        "lazy" => Some(SessionMode::Lazy),
+        "always" => Some(SessionMode::Always),
+        _ => None,
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    let val = value.trim().to_lowercase();
+    val == "on" || val == "true" || val == "yes"
+}
+
+/// Parse `off`, `epoch`, `max` or a duration such as `3600`, `30m`, `12h`, `7d`, `1y`
+fn parse_expires(value: &str) -> Option<Expires> {
+    match value.to_lowercase().as_str() {
+        "off" => Some(Expires::Off),
+        "epoch" => Some(Expires::Epoch),
+        "max" => Some(Expires::Max),
+        // Anything past `max` is capped to it
+        other => parse_duration_secs(other).map(|secs| Expires::After(secs.min(Expires::MAX_SECS))),
+    }
+}
+
+/// Parse a byte size such as `512`, `64k`, `10M` or `5G`
+fn parse_size(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last()?.to_ascii_lowercase() {
+        'k' => (&value[..value.len() - 1], 1024),
+        'm' => (&value[..value.len() - 1], 1024 * 1024),
+        'g' => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+fn parse_duration_secs(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last()? {
+        's' => (&value[..value.len() - 1], 1),
+        'm' => (&value[..value.len() - 1], 60),
+        'h' => (&value[..value.len() - 1], 60 * 60),
+        'd' => (&value[..value.len() - 1], 24 * 60 * 60),
+        'w' => (&value[..value.len() - 1], 7 * 24 * 60 * 60),
+        'y' => (&value[..value.len() - 1], 365 * 24 * 60 * 60),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64k"), Some(64 * 1024));
+        assert_eq!(parse_size(" 10M "), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("5G"), Some(5 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("99999999999999999999"), None);
+        assert_eq!(parse_size(&format!("{}G", usize::MAX / 1024)), None);
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size("-1k"), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration_secs("3600"), Some(3600));
+        assert_eq!(parse_duration_secs("12h"), Some(12 * 60 * 60));
+        assert_eq!(parse_duration_secs("30d"), Some(30 * 24 * 60 * 60));
+        assert_eq!(parse_duration_secs("99999999999999999999d"), None);
+        assert_eq!(parse_duration_secs("999999999999999999d"), None);
+        assert_eq!(parse_duration_secs(&format!("{}y", u64::MAX / (365 * 24 * 60 * 60) + 1)), None);
+        assert_eq!(parse_duration_secs("1x"), None);
+    }
+
+    #[test]
+    fn expires_is_capped_at_max() {
+        assert_eq!(parse_expires("1y"), Some(Expires::After(365 * 24 * 60 * 60)));
+        assert_eq!(parse_expires("500000y"), Some(Expires::After(Expires::MAX_SECS)));
+        assert_eq!(parse_expires("Max"), Some(Expires::Max));
+        assert_eq!(parse_expires("999999999999999999d"), None);
+    }
+
+    #[test]
+    fn auth_lines_without_equals() {
+        let config = parse_config_string("route /a {\n    auth_basic \"Staff only\"\n    auth_user_file /etc/htpasswd\n    root = www\n}\n").unwrap();
+        let auth = &config.routes[0].auth;
+        assert_eq!(auth.scheme, Some(AuthScheme::Basic("Staff only".to_string())));
+        assert_eq!(auth.user_file.as_deref(), Some("/etc/htpasswd"));
+
+        assert!(parse_config_string("route /a {\n    auth_basic\n}\n").is_err());
+    }
+
+    #[test]
+    fn other_route_lines_without_equals_are_skipped() {
+        let config = parse_config_string("route /a {\n    autoindex on\n    root = www\n}\n").unwrap();
+        assert_eq!(config.routes[0].root, "www");
+        assert!(!config.routes[0].autoindex);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::http::MimeTypes;
+
 /// Server configuration container
 #[derive(Debug)]
 pub struct ServerConfig {
@@ -6,6 +8,78 @@ pub struct ServerConfig {
     pub routes: Vec<RouteConfig>,
     pub error_path: String,
     pub vhosts: Vec<VHost>,
+    pub mime_types: MimeTypes,
+    pub default_type: String,
+    pub session: SessionConfig,
+}
+
+/// Session cookie and lifetime settings (`session_*` directives)
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub secure: bool,
+    pub same_site: SameSite,
+    /// Cookie `Max-Age`, and the longest a session lives however active;
+    /// a browser-session cookie if unset
+    pub max_age: Option<u64>,
+    /// Seconds without a request after which a session expires
+    pub idle_timeout: u64,
+    pub store: SessionBackend,
+    /// HMAC keys for the cookie (`session_secret`): the first signs, any
+    /// of them verifies. Unsigned cookies are used when empty.
+    pub secrets: Vec<String>,
+    /// `sessions` outside any vhost or route
+    pub mode: SessionMode,
+}
+
+/// When requests get a session (`sessions`, per route, vhost or server)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SessionMode {
+    /// Never: no session is resumed, passed to CGI or created
+    Off,
+    /// An existing session is resumed; a new one is only started when a
+    /// handler stores something in it
+    #[default]
+    Lazy,
+    /// Every response without a valid session cookie starts a session
+    Always,
+}
+
+/// Where sessions are kept (`session_store`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SessionBackend {
+    /// Lost on restart
+    #[default]
+    Memory,
+    /// One file per session in this directory, reloaded on startup
+    File(String),
+    /// Nothing kept on the server: the session lives in the cookie,
+    /// encrypted and authenticated with `session_secret`
+    Cookie,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: "SID".to_string(),
+            secure: false,
+            same_site: SameSite::Lax,
+            max_age: None,
+            idle_timeout: 30 * 60,
+            store: SessionBackend::Memory,
+            secrets: Vec::new(),
+            mode: SessionMode::Lazy,
+        }
+    }
+}
+
+/// `SameSite` attribute of the session cookie
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Sent cross-site too; browsers require `Secure` with it
+    None,
 }
 
 /// Virtual host configuration
@@ -14,16 +88,181 @@ pub struct VHost {
     pub name: String,
     pub error_path: String,
     pub routes: Vec<RouteConfig>,
+    /// Overrides the server-wide `sessions` for this vhost's routes
+    pub sessions: Option<SessionMode>,
+    /// Credentials every route of this vhost asks for, unless it says otherwise
+    pub auth: AuthConfig,
+}
+
+/// Access control (`auth_basic` / `auth_digest` / `auth_jwt` and their
+/// settings). Unset parts of a route's settings come from its vhost's.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthConfig {
+    pub scheme: Option<AuthScheme>,
+    /// htpasswd file for Basic, htdigest file for Digest
+    pub user_file: Option<String>,
+    pub jwt: JwtConfig,
+}
+
+impl AuthConfig {
+    /// These settings with the gaps filled from `fallback`
+    pub fn or(&self, fallback: &AuthConfig) -> AuthConfig {
+        AuthConfig {
+            scheme: self.scheme.clone().or_else(|| fallback.scheme.clone()),
+            user_file: self.user_file.clone().or_else(|| fallback.user_file.clone()),
+            jwt: self.jwt.or(&fallback.jwt),
+        }
+    }
+}
+
+/// How `auth_jwt` checks bearer tokens (`auth_jwt_*`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JwtConfig {
+    /// Shared secret for HS256 tokens
+    pub secret: Option<String>,
+    /// PEM public key for RS256 tokens
+    pub key_file: Option<String>,
+    /// Cookie holding the token when there's no `Authorization` header
+    pub cookie: Option<String>,
+    /// `aud` values accepted; any token if unset
+    pub audience: Option<Vec<String>>,
+    /// Claims passed to CGI scripts as `JWT_<NAME>`
+    pub claims: Option<Vec<String>>,
+}
+
+impl JwtConfig {
+    pub fn or(&self, fallback: &JwtConfig) -> JwtConfig {
+        JwtConfig {
+            secret: self.secret.clone().or_else(|| fallback.secret.clone()),
+            key_file: self.key_file.clone().or_else(|| fallback.key_file.clone()),
+            cookie: self.cookie.clone().or_else(|| fallback.cookie.clone()),
+            audience: self.audience.clone().or_else(|| fallback.audience.clone()),
+            claims: self.claims.clone().or_else(|| fallback.claims.clone()),
+        }
+    }
+}
+
+/// How a route asks for credentials
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthScheme {
+    /// `auth_basic = off`: open, even inside a protected vhost
+    Off,
+    /// RFC 7617 Basic, with this realm
+    Basic(String),
+    /// RFC 7616 Digest, with this realm
+    Digest(String),
+    /// RFC 6750 bearer tokens, checked as JWTs (RFC 7519), with this realm
+    Jwt(String),
 }
 
 /// Route configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RouteConfig {
     pub path: String,
     pub methods: Vec<String>,
     pub root: String,
     pub default_file: Option<String>,
     pub autoindex: bool,
+    pub autoindex_format: AutoindexFormat,
+    pub autoindex_archive: bool,
     pub cgi: Option<String>,
     pub redirect: Option<String>,
+    pub cache_control: Vec<ExtRule<String>>,
+    pub expires: Vec<ExtRule<Expires>>,
+    pub mime_types: Vec<ExtRule<String>>,
+    pub default_type: Option<String>,
+    pub gzip: bool,
+    pub gzip_min_length: usize,
+    pub gzip_static: bool,
+    pub try_files: Vec<String>,
+    pub upload_conflict: UploadConflict,
+    /// Let PUT create missing parent directories
+    pub create_dirs: bool,
+    /// Let DELETE remove non-empty directories
+    pub delete_recursive: bool,
+    /// DELETE moves to `<root>/.trash/` instead of removing
+    pub trash: bool,
+    /// Seconds trashed files are kept before the sweep purges them;
+    /// forever if unset
+    pub trash_retention: Option<u64>,
+    /// Accept resumable uploads (tus 1.0) on this route
+    pub tus: bool,
+    /// Serve WebDAV class 1 (PROPFIND, MKCOL, COPY, MOVE, ...) on this route
+    pub webdav: bool,
+    /// Extensions (lowercase, no dot) uploads must have; empty allows any
+    pub upload_allow_ext: Vec<String>,
+    /// Extensions uploads must not have
+    pub upload_deny_ext: Vec<String>,
+    /// Most files the route root may hold
+    pub upload_max_files: Option<usize>,
+    /// Most bytes the route root may hold
+    pub upload_dir_quota: Option<u64>,
+    /// Check uploaded content against its extension's magic number
+    pub upload_sniff: bool,
+    /// Overrides the vhost's or server's `sessions`
+    pub sessions: Option<SessionMode>,
+    pub auth: AuthConfig,
+}
+
+/// Output format of directory listings
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AutoindexFormat {
+    #[default]
+    Html,
+    Json,
+    Text,
+}
+
+/// What an upload does when the target name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UploadConflict {
+    #[default]
+    Overwrite,
+    /// Store under `name-1.ext`, `name-2.ext`, ...
+    Rename,
+    /// Refuse with 409 Conflict
+    Reject,
+}
+
+/// A directive value, optionally restricted to a set of file extensions
+/// (`cache_control .js .css = ...`). An empty extension list matches any file.
+#[derive(Debug, Clone)]
+pub struct ExtRule<T> {
+    pub extensions: Vec<String>,
+    pub value: T,
+}
+
+/// Value of the `expires` directive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expires {
+    /// Don't emit an Expires header
+    Off,
+    /// Already expired (Thu, 01 Jan 1970)
+    Epoch,
+    /// Far future (10 years)
+    Max,
+    /// Seconds from the time of the response, at most `MAX_SECS`
+    After(u64),
+}
+
+impl Expires {
+    /// How far ahead `max` points, and the cap for `After`
+    pub const MAX_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+}
+
+/// Pick the rule that applies to `file_path`: an extension-specific rule wins
+/// over a catch-all one. Directories (`None`) only match catch-all rules.
+pub fn match_ext_rule<'a, T>(rules: &'a [ExtRule<T>], file_path: Option<&str>) -> Option<&'a T> {
+    let ext = file_path
+        .and_then(|p| std::path::Path::new(p).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    if let Some(ext) = ext
+        && let Some(rule) = rules.iter().find(|r| r.extensions.contains(&ext))
+    {
+        return Some(&rule.value);
+    }
+
+    rules.iter().find(|r| r.extensions.is_empty()).map(|r| &r.value)
 }
@@ -0,0 +1,65 @@
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Standard base64 with `=` padding (RFC 4648 §4)
+pub fn base64_encode(data: &[u8]) -> String {
+    encode(data, STANDARD, true)
+}
+
+/// URL-safe base64 without padding (RFC 4648 §5), as used by JWTs
+pub fn base64url_encode(data: &[u8]) -> String {
+    encode(data, URL_SAFE, false)
+}
+
+/// Decode standard base64; padding is optional. None on invalid input.
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    decode(text, STANDARD)
+}
+
+/// Decode URL-safe base64; padding is optional. None on invalid input.
+pub fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    decode(text, URL_SAFE)
+}
+
+fn encode(data: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        let symbols = chunk.len() + 1;
+
+        for i in 0..4 {
+            if i < symbols {
+                out.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else if pad {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode(text: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for byte in text.bytes() {
+        let value = alphabet.iter().position(|&c| c == byte)? as u32;
+        acc = acc << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    // A single leftover symbol can't encode a whole byte
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
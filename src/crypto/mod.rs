@@ -0,0 +1,28 @@
+mod base64;
+mod bcrypt;
+mod chacha20;
+mod hmac;
+mod md5;
+mod random;
+mod rsa;
+mod sha1;
+mod sha256;
+mod sha512;
+mod sha_crypt;
+
+pub use base64::{base64_decode, base64_encode, base64url_decode, base64url_encode};
+pub use bcrypt::bcrypt_verify;
+pub use chacha20::chacha20;
+pub use hmac::{constant_time_eq, hmac_sha256};
+pub use md5::{md5, Md5};
+pub use random::{fill_random, random_hex};
+pub use rsa::RsaPublicKey;
+pub use sha1::sha1;
+pub use sha256::{sha256, Sha256};
+pub use sha512::{sha512, Sha512};
+pub use sha_crypt::sha_crypt_verify;
+
+/// Lowercase hex encoding
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
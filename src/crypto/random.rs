@@ -0,0 +1,14 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+/// Fill `buf` from the kernel CSPRNG
+pub fn fill_random(buf: &mut [u8]) -> io::Result<()> {
+    File::open("/dev/urandom")?.read_exact(buf)
+}
+
+/// `n` random bytes as lowercase hex, for unguessable identifiers
+pub fn random_hex(n: usize) -> io::Result<String> {
+    let mut buf = vec![0u8; n];
+    fill_random(&mut buf)?;
+    Ok(super::hex(&buf))
+}
@@ -0,0 +1,387 @@
+use super::{base64_decode, constant_time_eq, sha256};
+
+/// DigestInfo prefix for SHA-256 in a PKCS#1 v1.5 signature (RFC 8017 §9.2)
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20,
+];
+
+/// OID 1.2.840.113549.1.1.1 (rsaEncryption), DER encoded
+const RSA_ENCRYPTION_OID: [u8; 11] = [0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// Smallest and largest modulus accepted, in bits
+const MIN_MODULUS_BITS: usize = 2048;
+const MAX_MODULUS_BITS: usize = 8192;
+
+/// An RSA public key
+#[derive(Debug, Clone)]
+pub struct RsaPublicKey {
+    /// Modulus, little-endian 32-bit limbs
+    n: Vec<u32>,
+    /// Public exponent, big-endian bytes
+    e: Vec<u8>,
+    /// Modulus length in bytes
+    size: usize,
+}
+
+impl RsaPublicKey {
+    /// Read a PEM `PUBLIC KEY` (SubjectPublicKeyInfo) or `RSA PUBLIC KEY`
+    /// (PKCS#1) block
+    pub fn from_pem(pem: &str) -> Option<Self> {
+        let (label, der) = pem_block(pem)?;
+        match label {
+            "PUBLIC KEY" => Self::from_spki(&der),
+            "RSA PUBLIC KEY" => Self::from_pkcs1(&der),
+            _ => None,
+        }
+    }
+
+    /// SubjectPublicKeyInfo: SEQUENCE { SEQUENCE { OID, params }, BIT STRING }
+    fn from_spki(der: &[u8]) -> Option<Self> {
+        let (0x30, info, _) = der_element(der)? else {
+            return None;
+        };
+        let (0x30, algorithm, rest) = der_element(info)? else {
+            return None;
+        };
+        if !algorithm.starts_with(&RSA_ENCRYPTION_OID) {
+            return None;
+        }
+        let (0x03, bits, _) = der_element(rest)? else {
+            return None;
+        };
+        // The first byte counts unused bits, which a key never has
+        match bits.split_first()? {
+            (0, key) => Self::from_pkcs1(key),
+            _ => None,
+        }
+    }
+
+    /// RSAPublicKey: SEQUENCE { INTEGER n, INTEGER e }
+    fn from_pkcs1(der: &[u8]) -> Option<Self> {
+        let (0x30, key, _) = der_element(der)? else {
+            return None;
+        };
+        let (0x02, n, rest) = der_element(key)? else {
+            return None;
+        };
+        let (0x02, e, _) = der_element(rest)? else {
+            return None;
+        };
+        let n = strip_zeros(n);
+        let e = strip_zeros(e);
+        let bits = n.len() * 8 - n.first()?.leading_zeros() as usize;
+        // RSA moduli are odd; Montgomery reduction depends on it
+        if !(MIN_MODULUS_BITS..=MAX_MODULUS_BITS).contains(&bits) || n[n.len() - 1] & 1 == 0 || e.is_empty() {
+            return None;
+        }
+        Some(RsaPublicKey { n: limbs(n, n.len().div_ceil(4)), e: e.to_vec(), size: n.len() })
+    }
+
+    /// Check an RSASSA-PKCS1-v1_5 signature with SHA-256 (RS256)
+    pub fn verify_sha256(&self, message: &[u8], signature: &[u8]) -> bool {
+        if signature.len() != self.size {
+            return false;
+        }
+        let s = limbs(signature, self.n.len());
+        if !less_than(&s, &self.n) {
+            return false;
+        }
+        let m = self.modpow(&s);
+
+        // EM = 00 01 FF..FF 00 || DigestInfo || hash
+        let mut expected = vec![0xff; self.size];
+        expected[0] = 0;
+        expected[1] = 1;
+        let suffix = [&[0][..], &SHA256_DIGEST_INFO, &sha256(message)].concat();
+        expected[self.size - suffix.len()..].copy_from_slice(&suffix);
+        constant_time_eq(&be_bytes(&m, self.size), &expected)
+    }
+
+    /// `base^e mod n`, in Montgomery form throughout
+    fn modpow(&self, base: &[u32]) -> Vec<u32> {
+        let n = &self.n;
+        let n_inv = montgomery_inverse(n[0]);
+        let r2 = r_squared(n);
+        let mut one = vec![0u32; n.len()];
+        one[0] = 1;
+
+        let base = mont_mul(base, &r2, n, n_inv);
+        let mut acc = mont_mul(&one, &r2, n, n_inv);
+        for byte in &self.e {
+            for bit in (0..8).rev() {
+                acc = mont_mul(&acc, &acc, n, n_inv);
+                if byte >> bit & 1 == 1 {
+                    acc = mont_mul(&acc, &base, n, n_inv);
+                }
+            }
+        }
+        mont_mul(&acc, &one, n, n_inv)
+    }
+}
+
+/// The label and decoded body of the first PEM block in `pem`
+fn pem_block(pem: &str) -> Option<(&str, Vec<u8>)> {
+    let start = pem.find("-----BEGIN ")? + "-----BEGIN ".len();
+    let label_end = start + pem[start..].find("-----")?;
+    let label = &pem[start..label_end];
+    let body_start = label_end + "-----".len();
+    let end = body_start + pem[body_start..].find(&format!("-----END {}-----", label))?;
+    let body: String = pem[body_start..end].chars().filter(|c| !c.is_whitespace()).collect();
+    Some((label, base64_decode(&body)?))
+}
+
+/// One DER element: its tag, contents and the bytes after it
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        // Long form: the low bits count the length bytes that follow
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count].iter().fold(0usize, |len, &b| len << 8 | b as usize);
+        (len, &rest[count..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+fn strip_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// Big-endian bytes as `count` little-endian limbs
+fn limbs(bytes: &[u8], count: usize) -> Vec<u32> {
+    let mut out = vec![0u32; count];
+    for (i, &byte) in bytes.iter().rev().enumerate() {
+        out[i / 4] |= (byte as u32) << (8 * (i % 4));
+    }
+    out
+}
+
+/// Limbs as exactly `len` big-endian bytes
+fn be_bytes(limbs: &[u32], len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    for (i, byte) in out.iter_mut().rev().enumerate() {
+        *byte = (limbs[i / 4] >> (8 * (i % 4))) as u8;
+    }
+    out
+}
+
+/// `a < b`, where `a` may carry one extra limb
+fn less_than(a: &[u32], b: &[u32]) -> bool {
+    for i in (0..a.len().max(b.len())).rev() {
+        let (x, y) = (a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0));
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
+/// `a -= b`, for `a >= b`
+fn sub_assign(a: &mut [u32], b: &[u32]) {
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let diff = *limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        *limb = diff as u32;
+        borrow = (diff < 0) as i64;
+    }
+}
+
+/// `-n0^-1 mod 2^32`, by Newton's iteration
+fn montgomery_inverse(n0: u32) -> u32 {
+    let mut inv = 1u32;
+    for _ in 0..5 {
+        inv = inv.wrapping_mul(2u32.wrapping_sub(n0.wrapping_mul(inv)));
+    }
+    inv.wrapping_neg()
+}
+
+/// `R^2 mod n` for `R = 2^(32 * limbs)`, by repeated doubling
+fn r_squared(n: &[u32]) -> Vec<u32> {
+    let mut r = vec![0u32; n.len() + 1];
+    r[0] = 1;
+    for _ in 0..64 * n.len() {
+        let mut carry = 0;
+        for limb in r.iter_mut() {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if !less_than(&r, n) {
+            sub_assign(&mut r, n);
+        }
+    }
+    r.truncate(n.len());
+    r
+}
+
+/// Montgomery product `a * b * R^-1 mod n` (CIOS), for `a, b < n`
+fn mont_mul(a: &[u32], b: &[u32], n: &[u32], n_inv: u32) -> Vec<u32> {
+    let s = n.len();
+    let mut t = vec![0u32; s + 2];
+
+    for &ai in a {
+        let mut carry = 0u64;
+        for j in 0..s {
+            let sum = t[j] as u64 + ai as u64 * b[j] as u64 + carry;
+            t[j] = sum as u32;
+            carry = sum >> 32;
+        }
+        let sum = t[s] as u64 + carry;
+        t[s] = sum as u32;
+        t[s + 1] = (sum >> 32) as u32;
+
+        // Add a multiple of n that clears the low limb, then drop it
+        let m = t[0].wrapping_mul(n_inv);
+        let mut carry = (t[0] as u64 + m as u64 * n[0] as u64) >> 32;
+        for j in 1..s {
+            let sum = t[j] as u64 + m as u64 * n[j] as u64 + carry;
+            t[j - 1] = sum as u32;
+            carry = sum >> 32;
+        }
+        let sum = t[s] as u64 + carry;
+        t[s - 1] = sum as u32;
+        t[s] = t[s + 1] + (sum >> 32) as u32;
+        t[s + 1] = 0;
+    }
+
+    t.truncate(s + 1);
+    if !less_than(&t, n) {
+        sub_assign(&mut t, n);
+    }
+    t.truncate(s);
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::base64url_decode;
+
+    /// The RSA key from RFC 7515 appendix A.2, as SubjectPublicKeyInfo
+    const RFC7515_SPKI: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAofgWCuLjybRlzo0tZWJj
+NiuSfb4p4fAkd/wWJcyQoTbji9k0l8W26mPddxHmfHQp+Vaw+4qPCJrcS2mJPMEz
+P1Pt0Bm4d4QlL+yRT+SFd2lZS+pCgNMsD1W/YpRPEwOWvG6b32690r2jZ47soMZo
+9wGzjb/7OMg0LOL+bSf63kpaSHSXndS5z5rexMdbBYUsLA9e+KXBdQOS+UTo7WTB
+EMa2R2CapHg665xsmtdVMTBQY4uDZlxvb3qCo5ZwKh9kG4LT6/I5IhlJH7aGhyxX
+FvUK+DWNmoudF8NAco9/h9iaGNj8q2ethFkMLs91kzk2PAcDTW9gb54h4FRWyuXp
+oQIDAQAB
+-----END PUBLIC KEY-----
+";
+
+    /// The same key as PKCS#1
+    const RFC7515_PKCS1: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEAofgWCuLjybRlzo0tZWJjNiuSfb4p4fAkd/wWJcyQoTbji9k0l8W2
+6mPddxHmfHQp+Vaw+4qPCJrcS2mJPMEzP1Pt0Bm4d4QlL+yRT+SFd2lZS+pCgNMs
+D1W/YpRPEwOWvG6b32690r2jZ47soMZo9wGzjb/7OMg0LOL+bSf63kpaSHSXndS5
+z5rexMdbBYUsLA9e+KXBdQOS+UTo7WTBEMa2R2CapHg665xsmtdVMTBQY4uDZlxv
+b3qCo5ZwKh9kG4LT6/I5IhlJH7aGhyxXFvUK+DWNmoudF8NAco9/h9iaGNj8q2et
+hFkMLs91kzk2PAcDTW9gb54h4FRWyuXpoQIDAQAB
+-----END RSA PUBLIC KEY-----
+";
+
+    /// RFC 7515 A.2.1: the JWS Signing Input and its signature
+    const RFC7515_INPUT: &str = "eyJhbGciOiJSUzI1NiJ9.eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ";
+    const RFC7515_SIGNATURE: &str = "cC4hiUPoj9Eetdgtv3hF80EGrhuB__dzERat0XF9g2VtQgr9PJbu3XOiZj5RZmh7AAuHIm4Bh-0Qc_lF5YKt_O8W2Fp5jujGbds9uJdbF9CUAr7t1dnZcAcQjbKBYNX4BAynRFdiuB--f_nZLgrnbyTyWzO75vRK5h6xBArLIARNPvkSjtQBMHlb1L07Qe7K0GarZRmB_eSN9383LcOLn6_dO--xi12jzDwusC-eOkHWEsqtFZESc6BfI7noOPqvhJ1phCnvWh6IeYI2w9QOYEUipUTI8np6LbgGY9Fs98rqVt5AXLIhWkWywlVmtVrBp0igcN_IoypGlUPQGe77Rw";
+
+    /// A 3072-bit key with e = 3, and its openssl signature of "localhost"
+    const E3_PKCS1: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIIBiAKCAYEAzq+MJNpaYEJ4WoEInkVhr3XXMpqXn2yGSp/ewILE3i5Kx654N3Vj
+2B+p8bEInsaaBCPTjzHoSzQl9ZOMA6glXdavIphU3ilNKMCumyNt1sorOi+leozD
+zogT3cdM4U/segwHRgY+2Ba0yfGhzqcQRgMZvEHPBWMK36iQzL5nYoF0MQ9GvAsq
+GDQlhhVb6D/qCAzpN/2euSTKWrZe0I78JwyyZVRLJpCzWpijf7FmTq4Q9fhJum2W
+ZKPQ8AimMEycqRwqGtB4tCJ6x8dJsJCsNs+XYn8nfZkIdLEsyEjFCtEkJjZ9yx78
+EdlUWFzFV2sJ+ARIdV6clfM0wdpbBtWa2IymJl0uI8GWaJJWf3YTElXokt26j74u
+RMqvcH6wVJ+X6YbW0Tdse5DFl7PTzRhPdlfiPVBRV5ay02bXvEl/zaodlP26+A5G
+I2SqgMg/OiXuQtAxezMmgPQMixEHWCRdvOvka2MXjrvQiOxoqapPoTcsLvqIxFGn
+eAvoTaxvkJcHAgED
+-----END RSA PUBLIC KEY-----
+";
+    const E3_SIGNATURE: &str = "xwbbcRKJ0FQzzBQZ9AJjhFSChmMn4X8adoi0zUYz9Qs6Fqm8edP4z9lBWPLgg9yZ3QbkQOoffGjCk9yQ00-FE1kJHY2r4-Ro1gUouNvMXoqhwnDu38qIXAGBLiCJNxX07akw5N3sH71MRH11VuxEizA2jlwmxlZNg6BslmDDrP1CFT2jQTuh0mW1k24mOzl2eKzS7FYJPknv4wX0aEq3T6hX1OYzvM2N3psBAlUBXcCFXoZ09YoKHE7GF9iYjKcJwgZGgsht8HOr3CuEd04_3kOB9TZ5amz2ZjwXo0m5RWPk4he6EY4zKedwEpwTr2cc_PG9Ycm9TZDRYWvKBqhlWDkcHDmiuqH9gOA6LI-xrrMeGCHwL5MM9OwuPbjbYWl-1VJPHxOropFGCYmeUkQVn1uM4UyzcKwol1kO1OVSsNyf2FJtUqT3yFKrwT6CbqATMUgTmfjAAeFibOzoPK7ulLMvcUTQpv2fTERRDIzKxsajLiKWDJvwB-SDuq9XVW43";
+
+    const SMALL_SPKI: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDBP/P1RhDf5shtGwDPqq8E5wHE
+twA4xHW/96phy0MjxP5qOGCznaejYXnlOnwRT/b6d4OFq5lLuo0SkmXgbWEKpgbb
+ETspJk0JGRvrNcDGO6+Nh1mtLIV1fOz+uao8LE3D5TWRGJXAQVdW1D5Vv1pNRgca
+7ZtV8+1H+xH3TGJ9iwIDAQAB
+-----END PUBLIC KEY-----
+";
+
+    fn signature(text: &str) -> Vec<u8> {
+        base64url_decode(text).expect("test signature decodes")
+    }
+
+    #[test]
+    fn rfc7515_rs256() {
+        let signature = signature(RFC7515_SIGNATURE);
+        for pem in [RFC7515_SPKI, RFC7515_PKCS1] {
+            let key = RsaPublicKey::from_pem(pem).expect("RFC 7515 key parses");
+            assert_eq!(key.size, 256);
+            assert!(key.verify_sha256(RFC7515_INPUT.as_bytes(), &signature));
+        }
+    }
+
+    #[test]
+    fn small_exponent_3072_bits() {
+        let key = RsaPublicKey::from_pem(E3_PKCS1).expect("3072-bit key parses");
+        assert_eq!(key.e, [3]);
+        assert!(key.verify_sha256(b"localhost", &signature(E3_SIGNATURE)));
+        assert!(!key.verify_sha256(b"localhosT", &signature(E3_SIGNATURE)));
+    }
+
+    #[test]
+    fn tampering_fails() {
+        let key = RsaPublicKey::from_pem(RFC7515_SPKI).expect("RFC 7515 key parses");
+        let signature = signature(RFC7515_SIGNATURE);
+        assert!(!key.verify_sha256(&RFC7515_INPUT.as_bytes()[1..], &signature));
+
+        let mut flipped = signature.clone();
+        flipped[100] ^= 1;
+        assert!(!key.verify_sha256(RFC7515_INPUT.as_bytes(), &flipped));
+        // Wrong length, even with only a leading zero added
+        assert!(!key.verify_sha256(RFC7515_INPUT.as_bytes(), &[&[0][..], &signature].concat()));
+        assert!(!key.verify_sha256(RFC7515_INPUT.as_bytes(), &signature[1..]));
+        // A signature must be below the modulus
+        assert!(!key.verify_sha256(RFC7515_INPUT.as_bytes(), &be_bytes(&key.n, key.size)));
+        assert!(!key.verify_sha256(RFC7515_INPUT.as_bytes(), &[0xff; 256]));
+    }
+
+    #[test]
+    fn rejects_unusable_keys() {
+        assert!(RsaPublicKey::from_pem(SMALL_SPKI).is_none());
+        assert!(RsaPublicKey::from_pem(&RFC7515_SPKI.replace("PUBLIC KEY", "PRIVATE KEY")).is_none());
+        assert!(RsaPublicKey::from_pem(&RFC7515_SPKI.replace("MIIBIjAN", "MIIBIjAA")).is_none());
+        assert!(RsaPublicKey::from_pem("").is_none());
+    }
+
+    #[test]
+    fn montgomery_arithmetic() {
+        for n0 in [1u32, 3, 0xfffffffb, 0x8000_0001, 0x1234_5679] {
+            assert_eq!(n0.wrapping_mul(montgomery_inverse(n0)), u32::MAX);
+        }
+
+        // One limb: R = 2^32
+        let n = [0xfffffffb];
+        assert_eq!(r_squared(&n), [0x19]);
+        assert_eq!(mont_mul(&[123456789], &[987654321], &n, montgomery_inverse(n[0])), [0xcdb10e92]);
+    }
+
+    #[test]
+    fn limb_conversions_round_trip() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05];
+        let limbs = limbs(&bytes, 2);
+        assert_eq!(limbs, [0x02030405, 0x01]);
+        assert_eq!(be_bytes(&limbs, 5), bytes);
+        assert!(less_than(&[5, 0, 0], &[6, 0]));
+        assert!(!less_than(&[0, 0, 1], &[6, 1]));
+    }
+}
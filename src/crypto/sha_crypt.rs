@@ -0,0 +1,245 @@
+use super::{Sha256, Sha512};
+
+/// crypt(3)'s base64 alphabet
+const ALPHABET: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+const DEFAULT_ROUNDS: u32 = 5000;
+const MIN_ROUNDS: u32 = 1000;
+const MAX_ROUNDS: u32 = 999_999_999;
+const MAX_SALT_LEN: usize = 16;
+
+/// Output byte order of `$5$` hashes, three bytes per four characters
+const SHA256_ORDER: [(usize, usize, usize); 10] = [
+    (0, 10, 20), (21, 1, 11), (12, 22, 2), (3, 13, 23), (24, 4, 14),
+    (15, 25, 5), (6, 16, 26), (27, 7, 17), (18, 28, 8), (9, 19, 29),
+];
+
+/// Output byte order of `$6$` hashes
+const SHA512_ORDER: [(usize, usize, usize); 21] = [
+    (0, 21, 42), (22, 43, 1), (44, 2, 23), (3, 24, 45), (25, 46, 4), (47, 5, 26), (6, 27, 48),
+    (28, 49, 7), (50, 8, 29), (9, 30, 51), (31, 52, 10), (53, 11, 32), (12, 33, 54), (34, 55, 13),
+    (56, 14, 35), (15, 36, 57), (37, 58, 16), (59, 17, 38), (18, 39, 60), (40, 61, 19), (62, 20, 41),
+];
+
+/// The hash functions SHA-crypt is defined over
+trait CryptHash {
+    fn new() -> Self;
+    fn update(&mut self, data: &[u8]);
+    fn digest(self) -> Vec<u8>;
+}
+
+impl CryptHash for Sha256 {
+    fn new() -> Self {
+        Sha256::new()
+    }
+    fn update(&mut self, data: &[u8]) {
+        Sha256::update(self, data)
+    }
+    fn digest(self) -> Vec<u8> {
+        self.finish().to_vec()
+    }
+}
+
+impl CryptHash for Sha512 {
+    fn new() -> Self {
+        Sha512::new()
+    }
+    fn update(&mut self, data: &[u8]) {
+        Sha512::update(self, data)
+    }
+    fn digest(self) -> Vec<u8> {
+        self.finish().to_vec()
+    }
+}
+
+/// Check `password` against a `$5$` (SHA-256) or `$6$` (SHA-512) crypt
+/// hash, with or without a `rounds=N$` setting
+pub fn sha_crypt_verify(password: &[u8], hash: &str) -> bool {
+    let Some((prefix, rest)) = hash.get(..3).zip(hash.get(3..)) else {
+        return false;
+    };
+
+    let (rounds, rest) = match rest.strip_prefix("rounds=").and_then(|r| r.split_once('$')) {
+        Some((rounds, rest)) => match rounds.parse::<u32>() {
+            Ok(rounds) => (Some(rounds.clamp(MIN_ROUNDS, MAX_ROUNDS)), rest),
+            Err(_) => return false,
+        },
+        None => (None, rest),
+    };
+    let Some((salt, expected)) = rest.split_once('$') else {
+        return false;
+    };
+    let salt = &salt.as_bytes()[..salt.len().min(MAX_SALT_LEN)];
+
+    let computed = match prefix {
+        "$5$" => encode(&sha_crypt::<Sha256>(password, salt, rounds.unwrap_or(DEFAULT_ROUNDS)), &SHA256_ORDER),
+        "$6$" => encode(&sha_crypt::<Sha512>(password, salt, rounds.unwrap_or(DEFAULT_ROUNDS)), &SHA512_ORDER),
+        _ => return false,
+    };
+    super::constant_time_eq(computed.as_bytes(), expected.as_bytes())
+}
+
+/// The raw digest of Drepper's SHA-crypt algorithm
+fn sha_crypt<H: CryptHash>(password: &[u8], salt: &[u8], rounds: u32) -> Vec<u8> {
+    let mut alternate = H::new();
+    alternate.update(password);
+    alternate.update(salt);
+    alternate.update(password);
+    let alternate = alternate.digest();
+    let hash_len = alternate.len();
+
+    let mut a = H::new();
+    a.update(password);
+    a.update(salt);
+    for chunk in repeat_to(&alternate, password.len()).chunks(hash_len) {
+        a.update(chunk);
+    }
+    // Each bit of the password length, lowest first
+    let mut n = password.len();
+    while n > 0 {
+        a.update(if n & 1 == 1 { &alternate } else { password });
+        n >>= 1;
+    }
+    let mut c = a.digest();
+
+    let mut dp = H::new();
+    for _ in 0..password.len() {
+        dp.update(password);
+    }
+    let p = repeat_to(&dp.digest(), password.len());
+
+    let mut ds = H::new();
+    for _ in 0..16 + c[0] as usize {
+        ds.update(salt);
+    }
+    let s = repeat_to(&ds.digest(), salt.len());
+
+    for round in 0..rounds {
+        let mut hasher = H::new();
+        hasher.update(if round % 2 == 1 { &p } else { &c });
+        if round % 3 != 0 {
+            hasher.update(&s);
+        }
+        if round % 7 != 0 {
+            hasher.update(&p);
+        }
+        hasher.update(if round % 2 == 1 { &c } else { &p });
+        c = hasher.digest();
+    }
+    c
+}
+
+/// `bytes` repeated (and cut) to exactly `len` bytes
+fn repeat_to(bytes: &[u8], len: usize) -> Vec<u8> {
+    bytes.iter().copied().cycle().take(len).collect()
+}
+
+/// The digest as crypt base64, in the algorithm's byte order
+fn encode(digest: &[u8], order: &[(usize, usize, usize)]) -> String {
+    let mut out = String::new();
+    let mut push = |b2: u8, b1: u8, b0: u8, chars: usize| {
+        let mut w = (b2 as u32) << 16 | (b1 as u32) << 8 | b0 as u32;
+        for _ in 0..chars {
+            out.push(ALPHABET[(w & 0x3f) as usize] as char);
+            w >>= 6;
+        }
+    };
+
+    for &(i, j, k) in order {
+        push(digest[i], digest[j], digest[k], 4);
+    }
+    // What's left after the groups of three
+    match digest.len() {
+        32 => push(0, digest[31], digest[30], 3),
+        _ => push(0, 0, digest[63], 2),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test strings from Drepper's "Unix crypt using SHA-256 and SHA-512"
+    const SHA256_VECTORS: [(&str, &str); 7] = [
+        ("Hello world!", "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"),
+        ("Hello world!", "$5$rounds=10000$saltstringsaltst$3xv.VbSHBb41AL9AvLeujZkZRBAwqFMz2.opqey6IcA"),
+        ("This is just a test", "$5$rounds=5000$toolongsaltstrin$Un/5jzAHMgOGZ5.mWJpuVolil07guHPvOW8mGRcvxa5"),
+        (
+            "a very much longer text to encrypt.  This one even stretches over morethan one line.",
+            "$5$rounds=1400$anotherlongsalts$Rx.j8H.h8HjEDGomFU8bDkXm3XIUnzyxf12oP84Bnq1",
+        ),
+        (
+            "we have a short salt string but not a short password",
+            "$5$rounds=77777$short$JiO1O3ZpDAxGJeaDIuqCoEFysAe1mZNJRs3pw0KQRd/",
+        ),
+        ("a short string", "$5$rounds=123456$asaltof16chars..$gP3VQ/6X7UUEW3HkBn2w1/Ptq2jxPyzV/cZKmF/wJvD"),
+        ("the minimum number is still observed", "$5$rounds=1000$roundstoolow$yfvwcWrQ8l/K0DAWyuPMDNHpIVlTQebY9l/gL972bIC"),
+    ];
+
+    const SHA512_VECTORS: [(&str, &str); 7] = [
+        (
+            "Hello world!",
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1",
+        ),
+        (
+            "Hello world!",
+            "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v.",
+        ),
+        (
+            "This is just a test",
+            "$6$rounds=5000$toolongsaltstrin$lQ8jolhgVRVhY4b5pZKaysCLi0QBxGoNeKQzQ3glMhwllF7oGDZxUhx1yxdYcz/e1JSbq3y6JMxxl8audkUEm0",
+        ),
+        (
+            "a very much longer text to encrypt.  This one even stretches over morethan one line.",
+            "$6$rounds=1400$anotherlongsalts$POfYwTEok97VWcjxIiSOjiykti.o/pQs.wPvMxQ6Fm7I6IoYN3CmLs66x9t0oSwbtEW7o7UmJEiDwGqd8p4ur1",
+        ),
+        (
+            "we have a short salt string but not a short password",
+            "$6$rounds=77777$short$WuQyW2YR.hBNpjjRhpYD/ifIw05xdfeEyQoMxIXbkvr0gge1a1x3yRULJ5CCaUeOxFmtlcGZelFl5CxtgfiAc0",
+        ),
+        (
+            "a short string",
+            "$6$rounds=123456$asaltof16chars..$BtCwjqMJGx5hrJhZywWvt0RLE8uZ4oPwcelCjmw2kSYu.Ec6ycULevoBK25fs2xXgMNrCzIMVcgEJAstJeonj1",
+        ),
+        (
+            "the minimum number is still observed",
+            "$6$rounds=1000$roundstoolow$kUMsbe306n21p9R.FRkW3IGn.S9NPN0x50YhH1xhLsPuWGsUSklZt58jaTfF4ZEQpyUNGc0dqbpBYYBaHHrsX.",
+        ),
+    ];
+
+    #[test]
+    fn sha256_known_answers() {
+        for (password, hash) in SHA256_VECTORS {
+            assert!(sha_crypt_verify(password.as_bytes(), hash), "{hash}");
+        }
+    }
+
+    #[test]
+    fn sha512_known_answers() {
+        for (password, hash) in SHA512_VECTORS {
+            assert!(sha_crypt_verify(password.as_bytes(), hash), "{hash}");
+        }
+    }
+
+    #[test]
+    fn rounds_below_minimum_are_raised() {
+        // The spec's input for the last vector asks for rounds=10
+        let (password, hash) = SHA256_VECTORS[6];
+        assert!(sha_crypt_verify(password.as_bytes(), &hash.replacen("rounds=1000", "rounds=10", 1)));
+    }
+
+    #[test]
+    fn long_salts_are_truncated() {
+        let (password, hash) = SHA256_VECTORS[2];
+        assert!(sha_crypt_verify(password.as_bytes(), &hash.replacen("toolongsaltstrin", "toolongsaltstring", 1)));
+    }
+
+    #[test]
+    fn wrong_password_fails() {
+        assert!(!sha_crypt_verify(b"Hello world", SHA256_VECTORS[0].1));
+        assert!(!sha_crypt_verify(b"Hello world", SHA512_VECTORS[0].1));
+        assert!(!sha_crypt_verify(b"Hello world!", "$7$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"));
+        assert!(!sha_crypt_verify(b"Hello world!", "$5$rounds=x$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"));
+    }
+}
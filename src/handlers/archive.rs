@@ -0,0 +1,474 @@
+use crate::http::{crc32_update, utc_components, BodyStream, HttpResponse};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File data is read and sent in pieces of this size
+const READ_CHUNK: usize = 64 * 1024;
+const TAR_BLOCK: usize = 512;
+
+/// Sizes and offsets from here on only fit in ZIP64 fields; the classic
+/// 32-bit field then holds this marker
+const ZIP32_LIMIT: u64 = 0xffff_ffff;
+/// Entry counts from here on need the ZIP64 end record
+const ZIP16_LIMIT: usize = 0xffff;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Parse the value of `?archive=`
+    pub fn from_query(query: &str) -> Option<Self> {
+        query.split(['&', ';']).find_map(|pair| match pair.split_once('=') {
+            Some(("archive", "tar")) => Some(ArchiveFormat::Tar),
+            Some(("archive", "zip")) => Some(ArchiveFormat::Zip),
+            _ => None,
+        })
+    }
+}
+
+/// Respond with the directory tree at `dir_path` as a tar or zip archive.
+/// The archive is generated while it is being sent; nothing is written to
+/// disk or buffered beyond one read chunk. Entries whose real location is
+/// outside `root` (symlinks pointing elsewhere) are skipped.
+pub fn archive_directory(dir_path: &str, root: &str, format: ArchiveFormat) -> HttpResponse {
+    let dir = Path::new(dir_path);
+    let (canonical_dir, canonical_root) = match (dir.canonicalize(), Path::new(root).canonicalize()) {
+        (Ok(d), Ok(r)) if d.starts_with(&r) && d.is_dir() => (d, r),
+        _ => return HttpResponse::not_found(),
+    };
+
+    let base_name = canonical_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+
+    let (content_type, extension) = match format {
+        ArchiveFormat::Tar => ("application/x-tar", "tar"),
+        ArchiveFormat::Zip => ("application/zip", "zip"),
+    };
+
+    let stream = ArchiveStream {
+        format,
+        root: canonical_root,
+        pending: vec![(canonical_dir, format!("{}/", base_name))],
+        current: None,
+        zip_entries: Vec::new(),
+        offset: 0,
+        finished: false,
+    };
+
+    let mut resp = HttpResponse::ok();
+    resp.set_header("Content-Type", content_type);
+    resp.set_header(
+        "Content-Disposition",
+        &format!("attachment; filename=\"{}.{}\"", base_name.replace(['"', '\\'], "_"), extension),
+    );
+    resp.set_body_stream(Box::new(stream));
+    resp
+}
+
+/// File currently being copied into the archive
+struct OpenFile {
+    file: File,
+    name: String,
+    remaining: u64,
+    size: u64,
+    crc: u32,
+    mtime: SystemTime,
+    header_offset: u64,
+}
+
+/// Central directory record kept for the end of a zip archive
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u64,
+    mtime: SystemTime,
+    header_offset: u64,
+    is_dir: bool,
+}
+
+struct ArchiveStream {
+    format: ArchiveFormat,
+    root: PathBuf,
+    /// Stack of (filesystem path, archive name) still to visit
+    pending: Vec<(PathBuf, String)>,
+    current: Option<OpenFile>,
+    zip_entries: Vec<ZipEntry>,
+    /// Bytes produced so far (zip needs header offsets)
+    offset: u64,
+    finished: bool,
+}
+
+impl BodyStream for ArchiveStream {
+    fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        if self.finished {
+            return None;
+        }
+
+        let chunk = if self.current.is_some() {
+            self.continue_file()
+        } else if let Some((path, name)) = self.pending.pop() {
+            self.start_entry(&path, name)
+        } else {
+            self.finished = true;
+            self.trailer()
+        };
+
+        self.offset += chunk.len() as u64;
+        Some(chunk)
+    }
+}
+
+impl ArchiveStream {
+    /// Emit the header for the next entry; directories queue their children
+    fn start_entry(&mut self, path: &Path, name: String) -> Vec<u8> {
+        let meta = match fs::symlink_metadata(path) {
+            Ok(m) => m,
+            Err(_) => return Vec::new(),
+        };
+
+        // Symlinked files are included only if they resolve inside the root;
+        // symlinked directories are never followed (no loops)
+        let meta = if meta.file_type().is_symlink() {
+            match path.canonicalize() {
+                Ok(real) if real.starts_with(&self.root) => match fs::metadata(&real) {
+                    Ok(m) if m.is_file() => m,
+                    _ => return Vec::new(),
+                },
+                _ => return Vec::new(),
+            }
+        } else {
+            meta
+        };
+
+        let mtime = meta.modified().unwrap_or(UNIX_EPOCH);
+
+        if meta.is_dir() {
+            let mut children: Vec<(PathBuf, String)> = match fs::read_dir(path) {
+                Ok(entries) => entries
+                    .flatten()
+                    .map(|e| (e.file_name().to_string_lossy().to_string(), e))
+                    .filter(|(child_name, _)| !crate::handlers::is_internal_name(child_name))
+                    .map(|(child_name, e)| {
+                        let suffix = if e.file_type().is_ok_and(|t| t.is_dir()) { "/" } else { "" };
+                        (e.path(), format!("{}{}{}", name, child_name, suffix))
+                    })
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            // Stack: reverse so entries come out in name order
+            children.sort_by(|a, b| b.1.cmp(&a.1));
+            self.pending.extend(children);
+
+            return match self.format {
+                ArchiveFormat::Tar => tar_header(&name, 0, mtime, true),
+                ArchiveFormat::Zip => {
+                    let header = zip_local_header(&name, 0, mtime, true);
+                    self.zip_entries.push(ZipEntry {
+                        name,
+                        crc: 0,
+                        size: 0,
+                        mtime,
+                        header_offset: self.offset,
+                        is_dir: true,
+                    });
+                    header
+                }
+            };
+        }
+
+        if !meta.is_file() {
+            return Vec::new(); // sockets, devices, fifos
+        }
+
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(_) => return Vec::new(),
+        };
+
+        let size = meta.len();
+        let header = match self.format {
+            ArchiveFormat::Tar => tar_header(&name, size, mtime, false),
+            ArchiveFormat::Zip => zip_local_header(&name, size, mtime, false),
+        };
+
+        self.current = Some(OpenFile {
+            file,
+            name,
+            remaining: size,
+            size,
+            crc: 0,
+            mtime,
+            header_offset: self.offset,
+        });
+        header
+    }
+
+    /// Emit the next piece of the open file, or its trailer when done
+    fn continue_file(&mut self) -> Vec<u8> {
+        let open = match self.current.as_mut() {
+            Some(o) => o,
+            None => return Vec::new(),
+        };
+
+        if open.remaining > 0 {
+            let want = (open.remaining as usize).min(READ_CHUNK);
+            let mut buf = vec![0u8; want];
+            let n = open.file.read(&mut buf).unwrap_or(0);
+            if n == 0 {
+                // File shrank since its header was written: pad with zeros
+                // so the declared size still holds
+                buf.fill(0);
+            } else {
+                buf.truncate(n);
+            }
+            open.remaining -= buf.len() as u64;
+            open.crc = crc32_update(open.crc, &buf);
+            return buf;
+        }
+
+        let open = self.current.take().unwrap();
+        match self.format {
+            ArchiveFormat::Tar => {
+                let padding = (TAR_BLOCK - (open.size as usize % TAR_BLOCK)) % TAR_BLOCK;
+                vec![0u8; padding]
+            }
+            ArchiveFormat::Zip => {
+                let mut descriptor = Vec::with_capacity(24);
+                descriptor.extend_from_slice(&0x0807_4b50u32.to_le_bytes());
+                descriptor.extend_from_slice(&open.crc.to_le_bytes());
+                // 64-bit sizes when the local header had a ZIP64 field
+                if open.size >= ZIP32_LIMIT {
+                    descriptor.extend_from_slice(&open.size.to_le_bytes());
+                    descriptor.extend_from_slice(&open.size.to_le_bytes());
+                } else {
+                    descriptor.extend_from_slice(&(open.size as u32).to_le_bytes());
+                    descriptor.extend_from_slice(&(open.size as u32).to_le_bytes());
+                }
+                self.zip_entries.push(ZipEntry {
+                    name: open.name,
+                    crc: open.crc,
+                    size: open.size,
+                    mtime: open.mtime,
+                    header_offset: open.header_offset,
+                    is_dir: false,
+                });
+                descriptor
+            }
+        }
+    }
+
+    fn trailer(&mut self) -> Vec<u8> {
+        match self.format {
+            ArchiveFormat::Tar => vec![0u8; TAR_BLOCK * 2],
+            ArchiveFormat::Zip => {
+                let mut out = Vec::new();
+                for entry in &self.zip_entries {
+                    out.extend_from_slice(&zip_central_header(entry));
+                }
+                let central_size = out.len() as u64;
+                let central_offset = self.offset;
+                let count = self.zip_entries.len();
+
+                if count >= ZIP16_LIMIT || central_size >= ZIP32_LIMIT || central_offset >= ZIP32_LIMIT {
+                    // ZIP64 end of central directory record, then its locator
+                    let record_offset = central_offset + central_size;
+                    out.extend_from_slice(&0x0606_4b50u32.to_le_bytes());
+                    out.extend_from_slice(&44u64.to_le_bytes()); // size of the rest of the record
+                    out.extend_from_slice(&((3 << 8) | 45u16).to_le_bytes());
+                    out.extend_from_slice(&45u16.to_le_bytes());
+                    out.extend_from_slice(&[0; 8]); // disk numbers
+                    out.extend_from_slice(&(count as u64).to_le_bytes());
+                    out.extend_from_slice(&(count as u64).to_le_bytes());
+                    out.extend_from_slice(&central_size.to_le_bytes());
+                    out.extend_from_slice(&central_offset.to_le_bytes());
+
+                    out.extend_from_slice(&0x0706_4b50u32.to_le_bytes());
+                    out.extend_from_slice(&0u32.to_le_bytes());
+                    out.extend_from_slice(&record_offset.to_le_bytes());
+                    out.extend_from_slice(&1u32.to_le_bytes()); // total disks
+                }
+
+                // Fields that overflow hold the marker; readers take the ZIP64 values
+                let count = count.min(ZIP16_LIMIT) as u16;
+                out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+                out.extend_from_slice(&[0, 0, 0, 0]); // disk numbers
+                out.extend_from_slice(&count.to_le_bytes());
+                out.extend_from_slice(&count.to_le_bytes());
+                out.extend_from_slice(&(central_size.min(ZIP32_LIMIT) as u32).to_le_bytes());
+                out.extend_from_slice(&(central_offset.min(ZIP32_LIMIT) as u32).to_le_bytes());
+                out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+                out
+            }
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Write `value` as a NUL-terminated octal field filling `field`
+fn octal_field(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{:0width$o}", value, width = width);
+    let digits = digits.as_bytes();
+    let start = digits.len().saturating_sub(width);
+    field[..width].copy_from_slice(&digits[start..]);
+    field[width] = 0;
+}
+
+/// ustar header block; names that don't fit get a PAX `path` record first
+fn tar_header(name: &str, size: u64, mtime: SystemTime, is_dir: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    let bytes = name.as_bytes();
+
+    let short_name = if bytes.len() <= 100 {
+        name.to_string()
+    } else {
+        let record = pax_record("path", name);
+        out.extend_from_slice(&tar_block("././@PaxHeader", record.len() as u64, mtime, b'x'));
+        out.extend_from_slice(&record);
+        out.resize(out.len() + (TAR_BLOCK - record.len() % TAR_BLOCK) % TAR_BLOCK, 0);
+        // Fallback name for readers without PAX support
+        let tail = &bytes[bytes.len() - 99..];
+        String::from_utf8_lossy(tail).to_string()
+    };
+
+    let typeflag = if is_dir { b'5' } else { b'0' };
+    out.extend_from_slice(&tar_block(&short_name, size, mtime, typeflag));
+    out
+}
+
+fn tar_block(name: &str, size: u64, mtime: SystemTime, typeflag: u8) -> Vec<u8> {
+    let mut block = vec![0u8; TAR_BLOCK];
+    let name_bytes = name.as_bytes();
+    let len = name_bytes.len().min(100);
+    block[..len].copy_from_slice(&name_bytes[..len]);
+
+    let mode = if typeflag == b'5' { 0o755 } else { 0o644 };
+    octal_field(&mut block[100..108], mode);
+    octal_field(&mut block[108..116], 0); // uid
+    octal_field(&mut block[116..124], 0); // gid
+    octal_field(&mut block[124..136], size);
+    octal_field(&mut block[136..148], unix_secs(mtime));
+    block[156] = typeflag;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+
+    // Checksum is computed with the checksum field filled with spaces
+    block[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = block.iter().map(|&b| b as u32).sum();
+    let digits = format!("{:06o}\0 ", checksum);
+    block[148..156].copy_from_slice(digits.as_bytes());
+    block
+}
+
+/// A PAX record: "<len> <key>=<value>\n", where len counts itself
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len() + 1;
+    while len.to_string().len() + body.len() != len {
+        len = len.to_string().len() + body.len();
+    }
+    format!("{}{}", len, body).into_bytes()
+}
+
+fn dos_datetime(time: SystemTime) -> (u16, u16) {
+    let (year, month, day, hour, minute, second) = utc_components(time);
+    let year = year.clamp(1980, 2107) as u16;
+    let time = ((hour as u16) << 11) | ((minute as u16) << 5) | (second as u16 / 2);
+    let date = ((year - 1980) << 9) | ((month as u16) << 5) | day as u16;
+    (time, date)
+}
+
+/// General purpose flags: bit 3 = sizes/CRC follow in a data descriptor,
+/// bit 11 = UTF-8 names
+fn zip_flags(is_dir: bool) -> u16 {
+    if is_dir { 1 << 11 } else { (1 << 3) | (1 << 11) }
+}
+
+/// ZIP64 extended information extra field (0x0001) holding `values`
+fn zip64_extra(values: &[u64]) -> Vec<u8> {
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let mut out = Vec::with_capacity(4 + 8 * values.len());
+    out.extend_from_slice(&0x0001u16.to_le_bytes());
+    out.extend_from_slice(&(8 * values.len() as u16).to_le_bytes());
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+/// "Version needed to extract": 4.5 when ZIP64 fields are used
+fn zip_version(zip64: bool) -> u16 {
+    if zip64 { 45 } else { 20 }
+}
+
+fn zip_local_header(name: &str, size: u64, mtime: SystemTime, is_dir: bool) -> Vec<u8> {
+    let (time, date) = dos_datetime(mtime);
+    let zip64 = size >= ZIP32_LIMIT;
+    let extra = if zip64 { zip64_extra(&[size, size]) } else { Vec::new() };
+
+    let mut out = Vec::with_capacity(30 + name.len() + extra.len());
+    out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+    out.extend_from_slice(&zip_version(zip64).to_le_bytes());
+    out.extend_from_slice(&zip_flags(is_dir).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // stored
+    out.extend_from_slice(&time.to_le_bytes());
+    out.extend_from_slice(&date.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // CRC: see data descriptor
+    out.extend_from_slice(&(size.min(ZIP32_LIMIT) as u32).to_le_bytes());
+    out.extend_from_slice(&(size.min(ZIP32_LIMIT) as u32).to_le_bytes());
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(&extra);
+    out
+}
+
+fn zip_central_header(entry: &ZipEntry) -> Vec<u8> {
+    let (time, date) = dos_datetime(entry.mtime);
+    let external_attrs: u32 = if entry.is_dir { (0o40755 << 16) | 0x10 } else { 0o100644 << 16 };
+
+    // Only the fields that overflow go in the ZIP64 extra field, in this order
+    let mut large = Vec::new();
+    if entry.size >= ZIP32_LIMIT {
+        large.extend([entry.size, entry.size]);
+    }
+    if entry.header_offset >= ZIP32_LIMIT {
+        large.push(entry.header_offset);
+    }
+    let extra = zip64_extra(&large);
+    let version = zip_version(!extra.is_empty());
+
+    let mut out = Vec::with_capacity(46 + entry.name.len() + extra.len());
+    out.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+    out.extend_from_slice(&((3 << 8) | version).to_le_bytes()); // made by: Unix
+    out.extend_from_slice(&version.to_le_bytes());
+    out.extend_from_slice(&zip_flags(entry.is_dir).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&time.to_le_bytes());
+    out.extend_from_slice(&date.to_le_bytes());
+    out.extend_from_slice(&entry.crc.to_le_bytes());
+    out.extend_from_slice(&(entry.size.min(ZIP32_LIMIT) as u32).to_le_bytes());
+    out.extend_from_slice(&(entry.size.min(ZIP32_LIMIT) as u32).to_le_bytes());
+    out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk
+    out.extend_from_slice(&0u16.to_le_bytes()); // internal attrs
+    out.extend_from_slice(&external_attrs.to_le_bytes());
+    out.extend_from_slice(&(entry.header_offset.min(ZIP32_LIMIT) as u32).to_le_bytes());
+    out.extend_from_slice(entry.name.as_bytes());
+    out.extend_from_slice(&extra);
+    out
+}
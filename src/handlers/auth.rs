@@ -0,0 +1,355 @@
+use crate::config::{AuthConfig, AuthScheme};
+use crate::crypto::{base64_decode, base64url_decode, base64url_encode, bcrypt_verify, constant_time_eq, fill_random, hex, hmac_sha256, md5, sha1, sha_crypt_verify};
+use crate::http::{HttpRequest, RemoteUser};
+use super::jwt::check_jwt;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A Digest nonce is good for this long; after that the client is told to
+/// retry with a fresh one (`stale=true`) rather than to ask for a password
+const NONCE_LIFETIME_SECS: u64 = 300;
+
+/// Bytes of the HMAC kept in a nonce
+const NONCE_MAC_LEN: usize = 16;
+
+/// Why a request on a protected route didn't get through
+#[derive(Debug)]
+pub enum AuthError {
+    /// 401 with this `WWW-Authenticate` challenge
+    Unauthorized(String),
+    /// 403: the credentials are good but not for this route
+    Forbidden(String),
+    /// The route's auth settings or user file are unusable (500)
+    Misconfigured,
+}
+
+/// Check the credentials `auth` asks for: the user the request
+/// authenticated as, or None on an open route
+pub fn check_credentials(request: &HttpRequest, auth: &AuthConfig) -> Result<Option<RemoteUser>, AuthError> {
+    let (realm, digest) = match &auth.scheme {
+        None | Some(AuthScheme::Off) => return Ok(None),
+        Some(AuthScheme::Basic(realm)) => (realm, false),
+        Some(AuthScheme::Digest(realm)) => (realm, true),
+        Some(AuthScheme::Jwt(realm)) => return check_jwt(request, realm, &auth.jwt).map(Some),
+    };
+    let Some(user_file) = &auth.user_file else {
+        eprintln!("[auth] {} asks for credentials but has no auth_user_file", request.path);
+        return Err(AuthError::Misconfigured);
+    };
+    // Read on every request, so edits to the file apply right away
+    let users = fs::read_to_string(user_file).map_err(|e| {
+        eprintln!("[auth] cannot read {}: {}", user_file, e);
+        AuthError::Misconfigured
+    })?;
+
+    if digest {
+        check_digest(request, realm, &users)
+    } else {
+        check_basic(request, realm, &users)
+    }
+}
+
+/// RFC 7617: `Authorization: Basic base64(user:password)`, checked against
+/// an htpasswd file
+fn check_basic(request: &HttpRequest, realm: &str, users: &str) -> Result<Option<RemoteUser>, AuthError> {
+    let challenge = || AuthError::Unauthorized(format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm));
+
+    let decoded = request
+        .header("Authorization")
+        .and_then(|value| credentials_of(value, "Basic"))
+        .and_then(|encoded| base64_decode(encoded.trim()))
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .ok_or_else(challenge)?;
+    let (name, password) = decoded.split_once(':').ok_or_else(challenge)?;
+
+    if htpasswd_entry(users, name).is_some_and(|hash| password_matches(password.as_bytes(), hash)) {
+        return Ok(Some(RemoteUser { name: name.to_string(), auth_type: "Basic", claims: Vec::new() }));
+    }
+    eprintln!("[auth] Basic login failed for '{}' on {}", name, request.path);
+    Err(challenge())
+}
+
+/// RFC 7616 with MD5 and `qop=auth`, checked against an htdigest file
+/// (`user:realm:MD5(user:realm:password)`)
+fn check_digest(request: &HttpRequest, realm: &str, users: &str) -> Result<Option<RemoteUser>, AuthError> {
+    let key = nonce_key()?;
+    let challenge = |stale: bool| {
+        let mut value = format!("Digest realm=\"{}\", qop=\"auth\", algorithm=MD5, nonce=\"{}\"", realm, new_nonce(key, realm));
+        if stale {
+            value.push_str(", stale=true");
+        }
+        AuthError::Unauthorized(value)
+    };
+
+    let params = match request.header("Authorization").and_then(|value| credentials_of(value, "Digest")) {
+        Some(params) => digest_params(params),
+        None => return Err(challenge(false)),
+    };
+    let field = |name: &str| params.get(name).map(String::as_str);
+
+    let (Some(name), Some(nonce), Some(uri), Some(response), Some(nc), Some(cnonce)) =
+        (field("username"), field("nonce"), field("uri"), field("response"), field("nc"), field("cnonce"))
+    else {
+        return Err(challenge(false));
+    };
+    let algorithm_ok = field("algorithm").is_none_or(|a| a.eq_ignore_ascii_case("MD5"));
+    // The credentials must be for this realm and this request
+    let uri_path = uri.split('?').next().unwrap_or(uri);
+    if field("realm") != Some(realm) || field("qop") != Some("auth") || !algorithm_ok || uri_path != request.path {
+        return Err(challenge(false));
+    }
+
+    let Some(ha1) = htdigest_entry(users, name, realm) else {
+        eprintln!("[auth] Digest login failed for '{}' on {}", name, request.path);
+        return Err(challenge(false));
+    };
+    let expected = digest_response(&ha1.to_lowercase(), nonce, nc, cnonce, &request.method, uri);
+    if !constant_time_eq(expected.as_bytes(), response.to_lowercase().as_bytes()) {
+        eprintln!("[auth] Digest login failed for '{}' on {}", name, request.path);
+        return Err(challenge(false));
+    }
+
+    // Right password; the nonce decides whether it still counts
+    match nonce_age(key, nonce, realm) {
+        Some(age) if age <= NONCE_LIFETIME_SECS => Ok(Some(RemoteUser { name: name.to_string(), auth_type: "Digest", claims: Vec::new() })),
+        Some(_) => Err(challenge(true)),
+        None => Err(challenge(false)),
+    }
+}
+
+/// The `response` a client sends for `qop=auth`:
+/// MD5(HA1:nonce:nc:cnonce:auth:MD5(method:uri)), in hex
+fn digest_response(ha1: &str, nonce: &str, nc: &str, cnonce: &str, method: &str, uri: &str) -> String {
+    let ha2 = hex(&md5(format!("{}:{}", method, uri).as_bytes()));
+    hex(&md5(format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2).as_bytes()))
+}
+
+/// What follows `<scheme> ` in an `Authorization` value
+fn credentials_of<'a>(value: &'a str, scheme: &str) -> Option<&'a str> {
+    let (name, rest) = value.trim().split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme).then_some(rest)
+}
+
+/// `username="bob", nc=00000001, ...` -> lowercase names to unquoted values
+fn digest_params(text: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = text;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let Some((name, after)) = rest.split_once('=') else {
+            break;
+        };
+        let after = after.trim_start();
+
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            // A quoted string, with backslash escapes
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            (value, &quoted[end..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+
+        params.insert(name.trim().to_ascii_lowercase(), value);
+        rest = remaining;
+    }
+    params
+}
+
+/// The hash stored for `name` in an htpasswd file (`user:hash` lines)
+fn htpasswd_entry<'a>(users: &'a str, name: &str) -> Option<&'a str> {
+    users
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.trim_end().split_once(':'))
+        .find(|(user, _)| *user == name)
+        .map(|(_, hash)| hash)
+}
+
+/// The hex HA1 stored for `name` in `realm` in an htdigest file
+fn htdigest_entry<'a>(users: &'a str, name: &str, realm: &str) -> Option<&'a str> {
+    users
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.trim_end().splitn(3, ':');
+            Some((fields.next()?, fields.next()?, fields.next()?))
+        })
+        .find(|(user, user_realm, _)| *user == name && *user_realm == realm)
+        .map(|(_, _, ha1)| ha1)
+}
+
+/// Check a password against an htpasswd hash: bcrypt (`$2y$`, `$2b$`,
+/// `$2a$`), SHA-crypt (`$5$`, `$6$`) or `{SHA}`
+fn password_matches(password: &[u8], hash: &str) -> bool {
+    if let Some(encoded) = hash.strip_prefix("{SHA}") {
+        return base64_decode(encoded).is_some_and(|expected| constant_time_eq(&sha1(password), &expected));
+    }
+    if hash.starts_with("$2") {
+        return bcrypt_verify(password, hash);
+    }
+    if hash.starts_with("$5$") || hash.starts_with("$6$") {
+        return sha_crypt_verify(password, hash);
+    }
+    let format = hash.split('$').nth(1).unwrap_or("plain text");
+    eprintln!("[auth] unsupported htpasswd hash format: {}", format);
+    false
+}
+
+/// Key for nonce MACs, new each time the server starts. Without a random
+/// source there is no key, and Digest logins fail rather than use a
+/// guessable one.
+fn nonce_key() -> Result<&'static [u8; 32], AuthError> {
+    static KEY: OnceLock<[u8; 32]> = OnceLock::new();
+    if let Some(key) = KEY.get() {
+        return Ok(key);
+    }
+    let mut key = [0u8; 32];
+    fill_random(&mut key).map_err(|e| {
+        eprintln!("[auth] cannot seed the nonce key: {}", e);
+        AuthError::Misconfigured
+    })?;
+    Ok(KEY.get_or_init(|| key))
+}
+
+/// A nonce carrying its creation time, MACed so only this server's
+/// nonces are accepted
+fn new_nonce(key: &[u8; 32], realm: &str) -> String {
+    let mut nonce = unix_time().to_be_bytes().to_vec();
+    let mac = hmac_sha256(key, &[&nonce, realm.as_bytes()].concat());
+    nonce.extend_from_slice(&mac[..NONCE_MAC_LEN]);
+    base64url_encode(&nonce)
+}
+
+/// Seconds since one of our nonces was issued; None if it isn't ours
+fn nonce_age(key: &[u8; 32], nonce: &str, realm: &str) -> Option<u64> {
+    let bytes = base64url_decode(nonce)?;
+    if bytes.len() != 8 + NONCE_MAC_LEN {
+        return None;
+    }
+    let (time, mac) = bytes.split_at(8);
+    let expected = hmac_sha256(key, &[time, realm.as_bytes()].concat());
+    if !constant_time_eq(&expected[..NONCE_MAC_LEN], mac) {
+        return None;
+    }
+    Some(unix_time().saturating_sub(u64::from_be_bytes(time.try_into().ok()?)))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: &str) -> HttpRequest {
+        let raw = format!("GET /dir/index.html HTTP/1.1\r\nHost: localhost\r\nAuthorization: {}\r\n\r\n", authorization);
+        HttpRequest::parse(raw.as_bytes()).expect("test request parses")
+    }
+
+    fn ha1(name: &str, realm: &str, password: &str) -> String {
+        hex(&md5(format!("{}:{}:{}", name, realm, password).as_bytes()))
+    }
+
+    #[test]
+    fn rfc2617_digest_response() {
+        // RFC 2617 section 3.5
+        let ha1 = ha1("Mufasa", "testrealm@host.com", "Circle Of Life");
+        let response = digest_response(&ha1, "dcd98b7102dd2f0e8b11d0f600bfb0c093", "00000001", "0a4f113b", "GET", "/dir/index.html");
+        assert_eq!(response, "6629fae49393a05397450978507c4ef1");
+    }
+
+    #[test]
+    fn rfc7616_digest_response() {
+        // RFC 7616 section 3.9.1, MD5
+        let ha1 = ha1("Mufasa", "http-auth@example.org", "Circle of Life");
+        let response = digest_response(
+            &ha1,
+            "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+            "00000001",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            "GET",
+            "/dir/index.html",
+        );
+        assert_eq!(response, "8ca523f5e9506fed4657c9700eebdbec");
+    }
+
+    #[test]
+    fn digest_login_with_our_nonce() {
+        let realm = "http-auth@example.org";
+        let users = format!("Mufasa:{}:{}\n", realm, ha1("Mufasa", realm, "Circle of Life"));
+        let nonce = new_nonce(nonce_key().unwrap(), realm);
+        let header = |password: &str| {
+            let response = digest_response(&ha1("Mufasa", realm, password), &nonce, "00000001", "0a4f113b", "GET", "/dir/index.html");
+            format!(
+                "Digest username=\"Mufasa\", realm=\"{}\", nonce=\"{}\", uri=\"/dir/index.html\", qop=auth, nc=00000001, cnonce=\"0a4f113b\", response=\"{}\"",
+                realm, nonce, response
+            )
+        };
+
+        let user = check_digest(&request(&header("Circle of Life")), realm, &users).ok().flatten();
+        assert_eq!(user.map(|u| u.name), Some("Mufasa".to_string()));
+        assert!(matches!(check_digest(&request(&header("circle of life")), realm, &users), Err(AuthError::Unauthorized(_))));
+        // Same credentials presented to another realm
+        assert!(check_digest(&request(&header("Circle of Life")), "other", &users).is_err());
+    }
+
+    #[test]
+    fn digest_rejects_foreign_nonce() {
+        // Correct response, but the nonce from RFC 7616 was never issued here
+        let realm = "http-auth@example.org";
+        let ha1 = ha1("Mufasa", realm, "Circle of Life");
+        let users = format!("Mufasa:{}:{}\n", realm, ha1);
+        let nonce = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        let header = format!(
+            "Digest username=\"Mufasa\", realm=\"{}\", uri=\"/dir/index.html\", algorithm=MD5, nonce=\"{}\", nc=00000001, cnonce=\"{}\", qop=auth, response=\"{}\"",
+            realm, nonce, cnonce, digest_response(&ha1, nonce, "00000001", cnonce, "GET", "/dir/index.html")
+        );
+        assert!(matches!(check_digest(&request(&header), realm, &users), Err(AuthError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn basic_login_against_htpasswd() {
+        let users = "# comment\n\
+            sha:{SHA}qUqP5cyxm6YcTAhz05Hph5gvu9M=\n\
+            bcrypt:$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW\n\
+            sha256:$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5\n\
+            plain:secret\n";
+        let login = |name: &str, password: &str| {
+            let header = format!("Basic {}", crate::crypto::base64_encode(format!("{}:{}", name, password).as_bytes()));
+            check_basic(&request(&header), "test", users).ok().flatten().map(|u| u.name)
+        };
+
+        assert_eq!(login("sha", "test"), Some("sha".to_string()));
+        assert_eq!(login("bcrypt", "U*U"), Some("bcrypt".to_string()));
+        assert_eq!(login("sha256", "Hello world!"), Some("sha256".to_string()));
+        assert_eq!(login("sha", "wrong"), None);
+        assert_eq!(login("nobody", "test"), None);
+        // Plain-text entries are never accepted
+        assert_eq!(login("plain", "secret"), None);
+    }
+}
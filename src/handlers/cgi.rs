@@ -1,5 +1,5 @@
 #![cfg(unix)]
-use crate::http::{HttpRequest, HttpResponse};
+use crate::http::{percent_encode_segment, HttpRequest, HttpResponse, SessionUpdate};
 use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
@@ -54,10 +54,32 @@ pub fn run_cgi(script_path: &str, path_info: &str, request: &HttpRequest) -> Htt
     
     // Pass HTTP headers as environment variables (CGI standard)
     for (k, v) in &request.headers {
+        // The server checked the credentials; scripts get the user instead
+        if request.remote_user.is_some() && k.eq_ignore_ascii_case("Authorization") {
+            continue;
+        }
         let env_key = format!("HTTP_{}", k.to_uppercase().replace("-", "_"));
         env.insert(env_key, v.clone());
     }
     
+    if let Some(user) = &request.remote_user {
+        if !user.name.is_empty() {
+            env.insert("REMOTE_USER".to_string(), user.name.clone());
+        }
+        env.insert("AUTH_TYPE".to_string(), user.auth_type.to_string());
+        // Forwarded token claims: `tenant-id` -> JWT_TENANT_ID
+        for (name, value) in &user.claims {
+            let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+            env.insert(format!("JWT_{}", name), value.clone());
+        }
+    }
+    
+    // The session: its ID, and its data form-encoded (`key=value&...`)
+    if let Some(session) = &request.session {
+        env.insert("SESSION_ID".to_string(), session.id.clone());
+        env.insert("SESSION_DATA".to_string(), session_data_string(&session.data));
+    }
+    
     // Note: Transfer-Encoding is already passed via HTTP_TRANSFER_ENCODING above
     // The body has already been decoded from chunked format by the HTTP parser,
     // so CONTENT_LENGTH reflects the actual decoded body size
@@ -171,26 +193,25 @@ pub fn run_cgi(script_path: &str, path_info: &str, request: &HttpRequest) -> Htt
         }
 
         // Process events
-        for i in 0..nfds as usize {
-            let ev = &events[i];
+        for ev in events.iter().take(nfds as usize) {
             let fd = ev.u64 as RawFd;
 
             // Handle stdin writes (to CGI process)
-            if let Some(ref mut stdin) = stdin_handle {
-                if fd == stdin.as_raw_fd() && !stdin_done {
-                    if (ev.events & libc::EPOLLOUT as u32) != 0 {
-                        match stdin.write(&body_to_write[body_written..]) {
-                            Ok(0) => stdin_done = true,
-                            Ok(n) => {
-                                body_written += n;
-                                if body_written >= body_to_write.len() {
-                                    stdin_done = true;
-                                }
-                            }
-                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                            Err(_) => stdin_done = true,
+            if let Some(ref mut stdin) = stdin_handle
+                && fd == stdin.as_raw_fd()
+                && !stdin_done
+                && (ev.events & libc::EPOLLOUT as u32) != 0
+            {
+                match stdin.write(&body_to_write[body_written..]) {
+                    Ok(0) => stdin_done = true,
+                    Ok(n) => {
+                        body_written += n;
+                        if body_written >= body_to_write.len() {
+                            stdin_done = true;
                         }
                     }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(_) => stdin_done = true,
                 }
             }
 
@@ -268,11 +289,17 @@ pub fn run_cgi(script_path: &str, path_info: &str, request: &HttpRequest) -> Htt
             
             // Handle Status header specially
             if key.eq_ignore_ascii_case("Status") {
-                if let Some((code_str, text)) = val.split_once(' ') {
-                    if let Ok(code) = code_str.parse::<u16>() {
-                        resp.status_code = code;
-                        resp.status_text = text.to_string();
-                    }
+                if let Some((code_str, text)) = val.split_once(' ')
+                    && let Ok(code) = code_str.parse::<u16>()
+                {
+                    resp.status_code = code;
+                    resp.status_text = text.to_string();
+                }
+            } else if is_session_header(key) {
+                // Meant for the server; never passed on to the client
+                match session_update(key, val) {
+                    Some(update) => resp.session_updates.push(update),
+                    None => eprintln!("[cgi] ignoring {}: {}", key, val),
                 }
             } else {
                 resp.set_header(key, val);
@@ -289,6 +316,43 @@ pub fn run_cgi(script_path: &str, path_info: &str, request: &HttpRequest) -> Htt
     resp
 }
 
+// Helper: Session data as `key=value&...`, percent-encoded, in key order
+fn session_data_string(data: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<_> = data.iter().collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode_segment(k), percent_encode_segment(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// Helper: `X-Session-*` headers are instructions to the server
+fn is_session_header(key: &str) -> bool {
+    key.get(..10).is_some_and(|prefix| prefix.eq_ignore_ascii_case("X-Session-"))
+}
+
+// Helper: `X-Session-Set: key=value`, `X-Session-Set: key=` (removes the
+// key) or `X-Session-Destroy`
+fn session_update(key: &str, val: &str) -> Option<SessionUpdate> {
+    if key.eq_ignore_ascii_case("X-Session-Destroy") {
+        return Some(SessionUpdate::Destroy);
+    }
+    if !key.eq_ignore_ascii_case("X-Session-Set") {
+        return None;
+    }
+    let (name, value) = val.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let value = value.trim();
+    if value.is_empty() {
+        return Some(SessionUpdate::Remove(name.to_string()));
+    }
+    Some(SessionUpdate::Set(name.to_string(), value.to_string()))
+}
+
 // Helper: Find Python interpreter
 fn find_python_interpreter() -> Option<String> {
     for prog in &["python3", "python"] {
@@ -1,29 +1,260 @@
-use crate::http::HttpResponse;
+use crate::config::AutoindexFormat;
+use crate::http::{add_vary, html_escape, json_string, percent_decode, percent_encode_path, percent_encode_segment, short_datetime, HttpRequest, HttpResponse, MimeTypes};
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn list_directory(path: &str, uri: &str, _route: &str) -> HttpResponse {
+/// One row of a directory listing
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    mime: String,
+}
+
+impl Entry {
+    /// Absolute, percent-encoded link to this entry
+    fn href(&self, base: &str) -> String {
+        let suffix = if self.is_dir { "/" } else { "" };
+        format!("{}{}{}", base, percent_encode_segment(&self.name), suffix)
+    }
+}
+
+/// Sort column, selected with Apache-style `?C=N|M|S|T`
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Modified,
+    Size,
+    Type,
+}
+
+impl SortKey {
+    fn letter(self) -> char {
+        match self {
+            SortKey::Name => 'N',
+            SortKey::Modified => 'M',
+            SortKey::Size => 'S',
+            SortKey::Type => 'T',
+        }
+    }
+}
+
+pub fn list_directory(path: &str, request: &HttpRequest, format: AutoindexFormat, mime: &MimeTypes) -> HttpResponse {
     let entries = match std::fs::read_dir(path) {
         Ok(e) => e,
         Err(_) => return HttpResponse::not_found(),
     };
-    
-    let mut html = format!("<h1>Index of {}</h1><ul>", uri);
-    
-    for entry in entries.flatten() {
-        let name = entry.file_name().to_string_lossy().to_string();
-        
-        let link = if uri == "/" || uri.ends_with('/') {
-            format!("{}{}", uri, name)
-        } else {
-            format!("{}/{}", uri, name)
-        };
-        
-        html.push_str(&format!(r#"<li><a href="{}">{}</a></li>"#, link, name));
-    }
-    
-    html.push_str("</ul>");
-    
+
+    let mut rows: Vec<Entry> = entries
+        .flatten()
+        .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry))
+        .filter(|(name, _)| !crate::handlers::is_internal_name(name))
+        .map(|(name, entry)| {
+            // Follow symlinks so a link to a directory is listed as one
+            let meta = std::fs::metadata(entry.path()).ok();
+            let is_dir = meta.as_ref().is_some_and(|m| m.is_dir());
+            Entry {
+                mime: if is_dir {
+                    "directory".to_string()
+                } else {
+                    mime.lookup(&name).unwrap_or("-").to_string()
+                },
+                size: meta.as_ref().map(|m| m.len()).unwrap_or(0),
+                modified: meta.and_then(|m| m.modified().ok()),
+                is_dir,
+                name,
+            }
+        })
+        .collect();
+
+    let (key, descending) = parse_sort(&request.query);
+    sort_entries(&mut rows, key, descending);
+
+    let display_uri = percent_decode(&request.path);
+    let base = base_href(&display_uri);
+
+    let (content_type, body) = match negotiate_format(request.header("Accept"), format) {
+        AutoindexFormat::Html => ("text/html; charset=utf-8", render_html(&rows, &display_uri, &base, key, descending)),
+        AutoindexFormat::Json => ("application/json", render_json(&rows, &base)),
+        AutoindexFormat::Text => ("text/plain; charset=utf-8", render_text(&rows)),
+    };
+
     let mut resp = HttpResponse::ok();
-    resp.set_header("Content-Type", "text/html");
-    resp.set_body(&html);
+    resp.set_header("Content-Type", content_type);
+    add_vary(&mut resp, "Accept");
+    resp.set_body(&body);
     resp
 }
+
+/// An explicit `Accept: application/json` or `text/plain` wins over the
+/// route's configured format; browsers' `text/html, */*` keep the default
+fn negotiate_format(accept: Option<&str>, configured: AutoindexFormat) -> AutoindexFormat {
+    let accept = accept.unwrap_or("").to_ascii_lowercase();
+    let first = accept.split(',').next().unwrap_or("").split(';').next().unwrap_or("").trim();
+
+    if accept.contains("application/json") {
+        AutoindexFormat::Json
+    } else if first == "text/plain" {
+        AutoindexFormat::Text
+    } else if first == "text/html" {
+        AutoindexFormat::Html
+    } else {
+        configured
+    }
+}
+
+fn render_html(rows: &[Entry], display_uri: &str, base: &str, key: SortKey, descending: bool) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>Index of {}</title>\n", html_escape(display_uri)));
+    html.push_str("<style>body{font-family:monospace}th,td{padding:2px 12px;text-align:left}td.size{text-align:right}</style>\n");
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>Index of {}</h1>\n<table>\n<tr>", html_escape(display_uri)));
+
+    for (column, label) in [
+        (SortKey::Name, "Name"),
+        (SortKey::Modified, "Last modified"),
+        (SortKey::Size, "Size"),
+        (SortKey::Type, "Type"),
+    ] {
+        // Clicking the active column flips its order
+        let order = if column == key && !descending { 'D' } else { 'A' };
+        html.push_str(&format!(
+            "<th><a href=\"?C={};O={}\">{}</a></th>",
+            column.letter(),
+            order,
+            label
+        ));
+    }
+    html.push_str("</tr>\n");
+
+    if !display_uri.trim_end_matches('/').is_empty() {
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">../</a></td><td></td><td class=\"size\">-</td><td>directory</td></tr>\n",
+            html_escape(&parent_href(display_uri))
+        ));
+    }
+
+    for row in rows {
+        let suffix = if row.is_dir { "/" } else { "" };
+        let modified = row.modified.map(short_datetime).unwrap_or_else(|| "-".to_string());
+        let size = if row.is_dir { "-".to_string() } else { human_size(row.size) };
+
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}{}</a></td><td>{}</td><td class=\"size\">{}</td><td>{}</td></tr>\n",
+            html_escape(&row.href(base)),
+            html_escape(&row.name),
+            suffix,
+            modified,
+            size,
+            html_escape(&row.mime)
+        ));
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn render_json(rows: &[Entry], base: &str) -> String {
+    let items: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let mtime = row
+                .modified
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs().to_string())
+                .unwrap_or_else(|| "null".to_string());
+            format!(
+                "{{\"name\":{},\"type\":\"{}\",\"size\":{},\"mtime\":{},\"href\":{}}}",
+                json_string(&row.name),
+                if row.is_dir { "directory" } else { "file" },
+                if row.is_dir { 0 } else { row.size },
+                mtime,
+                json_string(&row.href(base))
+            )
+        })
+        .collect();
+    format!("[{}]\n", items.join(",\n"))
+}
+
+/// One name per line; directories keep a trailing `/`
+fn render_text(rows: &[Entry]) -> String {
+    rows.iter()
+        .map(|row| format!("{}{}\n", row.name, if row.is_dir { "/" } else { "" }))
+        .collect()
+}
+
+/// Parse `C=<column>;O=<A|D>` (also accepts `&` as separator).
+/// Defaults to name, ascending.
+fn parse_sort(query: &str) -> (SortKey, bool) {
+    let mut key = SortKey::Name;
+    let mut descending = false;
+
+    for pair in query.split([';', '&']) {
+        match pair.split_once('=') {
+            Some(("C", "N")) => key = SortKey::Name,
+            Some(("C", "M")) => key = SortKey::Modified,
+            Some(("C", "S")) => key = SortKey::Size,
+            Some(("C", "T")) => key = SortKey::Type,
+            Some(("O", "D")) => descending = true,
+            Some(("O", "A")) => descending = false,
+            _ => {}
+        }
+    }
+
+    (key, descending)
+}
+
+/// Directories always come first; the chosen column orders within each group
+fn sort_entries(rows: &mut [Entry], key: SortKey, descending: bool) {
+    rows.sort_by(|a, b| {
+        let by_kind = b.is_dir.cmp(&a.is_dir);
+        if by_kind != Ordering::Equal {
+            return by_kind;
+        }
+
+        let by_key = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Type => a.mime.cmp(&b.mime),
+        };
+        let by_key = by_key.then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+
+        if descending { by_key.reverse() } else { by_key }
+    });
+}
+
+/// Encoded directory URI with a trailing slash, for building child links
+fn base_href(display_uri: &str) -> String {
+    let encoded = percent_encode_path(display_uri);
+    if encoded.ends_with('/') {
+        encoded
+    } else {
+        format!("{}/", encoded)
+    }
+}
+
+fn parent_href(display_uri: &str) -> String {
+    let trimmed = display_uri.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(pos) => percent_encode_path(&trimmed[..=pos]),
+        None => "/".to_string(),
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}", bytes)
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
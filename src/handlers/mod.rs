@@ -1,14 +1,29 @@
 #![cfg(unix)]
+mod archive;
 mod static_files;
 mod upload_file;
+mod upload_policy;
+mod put_file;
+mod tus;
 mod remove_file;
+mod trash;
+mod webdav;
 mod directory;
 mod session;
+mod auth;
+mod jwt;
 mod cgi;
 
-pub use static_files::serve_file;
-pub use upload_file::upload_file;
+pub use archive::{archive_directory, ArchiveFormat};
+pub use static_files::{file_etag, find_precompressed, serve_file};
+pub use upload_file::{create_upload_temp, store_upload, upload_file};
+pub use upload_policy::{check_extension, check_quota, check_upload};
+pub use put_file::put_file;
+pub use tus::{add_tus_options, handle_tus, is_tus_request};
 pub use remove_file::delete_file;
+pub use trash::{is_internal_name, is_restore_request, move_to_trash, restore_from_trash, sweep_trash};
+pub use webdav::{handle_webdav, is_webdav_method, DavContext};
 pub use directory::list_directory;
-pub use session::{create_session_id, get_session_id};
+pub use session::{clear_session_cookie, create_session_id, get_session_id, session_cookie, Session, SessionStore};
+pub use auth::{check_credentials, AuthError};
 pub use cgi::run_cgi;
@@ -0,0 +1,98 @@
+use crate::config::RouteConfig;
+use crate::http::{HttpRequest, HttpResponse};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use super::{check_upload, create_upload_temp, file_etag};
+
+/// Create or replace the file at `file_path` (already mapped under the
+/// route root) with the request body: 201 if it is new, 204 if it was
+/// replaced. Errors come back as a status code for the caller's error page.
+pub fn put_file(request: &HttpRequest, route: &RouteConfig, file_path: &str) -> Result<HttpResponse, u16> {
+    // Partial PUT is not supported; don't store a fragment as the whole file
+    if request.header("Content-Range").is_some() || request.path.ends_with('/') {
+        return Err(400);
+    }
+
+    let path = Path::new(file_path);
+    if path.is_dir() {
+        return Err(409);
+    }
+    let parent = path.parent().ok_or(403u16)?;
+
+    // Whatever already exists of the parent chain must really be under
+    // the root, or a symlinked directory could lead the write elsewhere
+    if !ancestors_within_root(parent, &route.root) {
+        return Err(403);
+    }
+
+    if !parent.is_dir() {
+        if !route.create_dirs {
+            return Err(409);
+        }
+        // Fails if some component is a regular file
+        fs::create_dir_all(parent).map_err(|_| 409u16)?;
+    }
+
+    let existed = fs::symlink_metadata(path).is_ok();
+
+    // Spooled into the route root while it was received, or written out
+    // now so it can be checked before it replaces anything
+    let temp_path = match &request.body_file {
+        Some(body_file) => body_file.path.clone(),
+        None => {
+            let dir = parent.to_string_lossy();
+            let written = create_upload_temp(&dir).and_then(|(temp_path, mut file)| {
+                let written = file.write_all(&request.body);
+                if written.is_err() {
+                    let _ = fs::remove_file(&temp_path);
+                }
+                written.map(|_| temp_path)
+            });
+            match written {
+                Ok(temp_path) => temp_path,
+                Err(e) => {
+                    eprintln!("[put] failed to write {}: {}", file_path, e);
+                    return Err(500);
+                }
+            }
+        }
+    };
+
+    let stored = check_upload(route, path, &temp_path, true).and_then(|_| {
+        fs::rename(&temp_path, path).map_err(|e| {
+            eprintln!("[put] failed to write {}: {}", file_path, e);
+            500u16
+        })
+    });
+    if let Err(code) = stored {
+        let _ = fs::remove_file(&temp_path);
+        return Err(code);
+    }
+
+    let mut response = if existed {
+        HttpResponse::new(204, "No Content")
+    } else {
+        let mut response = HttpResponse::new(201, "Created");
+        response.set_header("Location", &request.path);
+        response
+    };
+    if let Ok(metadata) = fs::metadata(path) {
+        response.set_header("ETag", &file_etag(&metadata));
+    }
+    Ok(response)
+}
+
+/// Check the deepest existing ancestor of `dir` resolves inside `root`
+pub(super) fn ancestors_within_root(dir: &Path, root: &str) -> bool {
+    let root = match fs::canonicalize(root) {
+        Ok(root) => root,
+        Err(_) => return false,
+    };
+
+    dir.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| fs::canonicalize(ancestor).ok())
+        .is_some_and(|real| real.starts_with(&root))
+}
@@ -1,47 +1,63 @@
-use std::path::{Path, PathBuf, Component};
+use crate::config::RouteConfig;
+use crate::http::HttpResponse;
 use std::fs;
-use crate::http::{HttpRequest, HttpResponse};
-
-pub fn delete_file(request: &HttpRequest) -> HttpResponse {
-    let file_path = extract_file_path(&request.path);
-    let full_path = build_safe_path("uploads", &file_path);
-    
-    if !is_safe_path(&full_path) {
-        return HttpResponse::forbidden();
-    }
-    
-    match fs::remove_file(&full_path) {
-        Ok(_) => HttpResponse::ok_with_message("Deleted"),
-        Err(_) => HttpResponse::not_found(),
+use std::io;
+use std::path::Path;
+
+use super::move_to_trash;
+use super::put_file::ancestors_within_root;
+
+/// Delete the file or directory at `file_path` (already mapped under the
+/// route root): 204 on success. A non-empty directory gives 409 unless
+/// the route has `delete_recursive = on`. With `trash = on` it is moved to
+/// the trash instead of removed. Errors come back as a status code for the
+/// caller's error page.
+pub fn delete_file(route: &RouteConfig, file_path: &str) -> Result<HttpResponse, u16> {
+    let path = Path::new(file_path);
+
+    // The root itself is configuration, not content
+    if path == Path::new(&route.root) {
+        return Err(403);
     }
-}
 
-fn extract_file_path(uri: &str) -> String {
-    let mut path = uri.trim_start_matches('/');
-    
-    if path.starts_with("upload") {
-        path = &path[6..];
-        path = path.trim_start_matches('/');
+    // Don't follow a symlink at the end of the path: deleting it removes
+    // the link, whatever it points to
+    let metadata = fs::symlink_metadata(path).map_err(|e| io_status(&e))?;
+
+    // But a symlinked directory along the way could lead outside the root
+    let parent = path.parent().ok_or(403u16)?;
+    if !ancestors_within_root(parent, &route.root) {
+        return Err(403);
     }
-    
-    path.to_string()
-}
 
-fn build_safe_path(base_dir: &str, relative: &str) -> PathBuf {
-    let mut result = PathBuf::from(base_dir);
-    
-    for component in Path::new(relative).components() {
-        match component {
-            Component::Normal(segment) => result.push(segment),
-            Component::CurDir => {},
-            _ => break,
+    let removed = if route.trash {
+        let non_empty = metadata.is_dir() && fs::read_dir(path).map_err(|e| io_status(&e))?.next().is_some();
+        if non_empty && !route.delete_recursive {
+            return Err(409);
+        }
+        move_to_trash(route, path)
+    } else if !metadata.is_dir() {
+        fs::remove_file(path)
+    } else if route.delete_recursive {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_dir(path)
+    };
+
+    match removed {
+        Ok(()) => Ok(HttpResponse::new(204, "No Content")),
+        Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => Err(409),
+        Err(e) => {
+            eprintln!("[delete] failed to remove {}: {}", file_path, e);
+            Err(io_status(&e))
         }
     }
-    
-    result
 }
 
-fn is_safe_path(path: &Path) -> bool {
-    path.starts_with("uploads") && 
-    !path.to_string_lossy().contains("..")
+fn io_status(e: &io::Error) -> u16 {
+    match e.kind() {
+        io::ErrorKind::NotFound => 404,
+        io::ErrorKind::PermissionDenied => 403,
+        _ => 500,
+    }
 }
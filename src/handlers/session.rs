@@ -1,24 +1,668 @@
-pub fn create_session_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    
-    format!("SID_{}", timestamp)
-}
-
-pub fn get_session_id(cookie_header: Option<&str>) -> Option<String> {
-    let header = cookie_header?;
-    
-    for part in header.split(';') {
-        let part = part.trim();
-        if part.starts_with("SID") { 
-            if let Some(value) = part.split('=').nth(1) {
-                return Some(value.trim().to_string());
+use crate::config::{SameSite, SessionConfig};
+use crate::crypto::{base64url_decode, base64url_encode, chacha20, constant_time_eq, fill_random, hmac_sha256, random_hex};
+use crate::http::{percent_decode, percent_encode_segment};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Random bytes in a session ID (hex encoded in the cookie)
+const SESSION_ID_BYTES: usize = 16;
+
+/// A request only refreshing a session's last access is written to disk at
+/// most this often; data changes are written right away
+const TOUCH_PERSIST_SECS: u64 = 60;
+
+/// Extension of session files in a `session_store = file:` directory
+const SESSION_EXT: &str = "session";
+
+/// Longest sealed session we hand out; browsers drop cookies over 4KB
+const MAX_SEALED_LEN: usize = 3800;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 32;
+
+/// One client's server-side session
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub created: SystemTime,
+    pub last_access: SystemTime,
+    pub expires: SystemTime,
+    pub data: HashMap<String, String>,
+    /// `last_access` as of the last write to disk
+    saved_access: SystemTime,
+}
+
+impl Session {
+    /// Record a request now and push the expiry out by the idle timeout,
+    /// but never past the session's maximum age
+    fn touch(&mut self, config: &SessionConfig) {
+        let now = SystemTime::now();
+        self.last_access = now;
+        self.expires = secs_after(now, config.idle_timeout);
+        if let Some(max_age) = config.max_age {
+            self.expires = self.expires.min(secs_after(self.created, max_age));
+        }
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires
+    }
+}
+
+/// Live sessions by ID, in memory and optionally mirrored to a directory
+#[derive(Debug, Default)]
+pub struct SessionStore {
+    sessions: HashMap<String, Session>,
+    /// Where each session is persisted as `<id>.session`, if anywhere
+    dir: Option<PathBuf>,
+    /// Sessions created, changed or removed since the last `flush`
+    dirty: HashSet<String>,
+    /// Sessions live in the client's cookie (`session_store = cookie`);
+    /// only the one being served is held here
+    stateless: bool,
+    /// Sessions whose cookie was signed with a retired key
+    resign: HashSet<String>,
+}
+
+impl SessionStore {
+    /// A store that lives in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store that keeps nothing: each session is sealed into its cookie
+    pub fn stateless() -> Self {
+        Self { stateless: true, ..Self::default() }
+    }
+
+    /// A store persisted to `dir`, loading the sessions already there.
+    /// Expired and unreadable session files are removed.
+    pub fn open(dir: &str) -> io::Result<Self> {
+        let dir = PathBuf::from(dir);
+        // Session data is private to the server
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+
+        let mut store = Self { dir: Some(dir.clone()), ..Self::default() };
+        let now = SystemTime::now();
+
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(SESSION_EXT) => {}
+                // A write interrupted by a crash; the old file is intact
+                Some("tmp") => {
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                _ => continue,
+            }
+
+            match load_session(&path) {
+                Some(session) if !session.is_expired(now) => {
+                    store.sessions.insert(session.id.clone(), session);
+                }
+                _ => {
+                    let _ = fs::remove_file(&path);
+                }
             }
         }
+        Ok(store)
+    }
+
+    /// The client's session from its `Cookie` header, marked as just used.
+    /// A cookie whose signature (or, in cookie mode, seal) doesn't check
+    /// out is ignored before the store is consulted.
+    pub fn resume(&mut self, cookie_header: Option<&str>, config: &SessionConfig) -> Option<&mut Session> {
+        let value = cookie_value(cookie_header, config)?;
+
+        if !self.stateless {
+            let id = get_session_id(cookie_header, config)?;
+            self.get(&id, config)?;
+            if value != sign_session_id(&id, config) {
+                self.resign.insert(id.clone());
+            }
+            return self.sessions.get_mut(&id);
+        }
+
+        let (session, current_key) = open_sealed(value, config)?;
+        if session.is_expired(SystemTime::now()) {
+            return None;
+        }
+        let id = session.id.clone();
+        if !current_key {
+            self.dirty.insert(id.clone());
+        }
+        self.sessions.insert(id.clone(), session);
+        self.get(&id, config)
+    }
+
+    /// The `Set-Cookie` value the client needs once a request is done: a
+    /// new session's ID, or in cookie mode the whole session whenever it
+    /// changed. Cookies signed with a retired key are renewed with the
+    /// current one. None while the client's cookie is still current.
+    pub fn outgoing_cookie(&mut self, id: &str, is_new: bool, config: &SessionConfig) -> Option<String> {
+        if !self.stateless {
+            let resign = self.resign.remove(id);
+            return (is_new || resign).then(|| session_cookie(&sign_session_id(id, config), config));
+        }
+
+        // Nothing stays on the server between requests
+        let session = self.sessions.remove(id)?;
+        if !self.dirty.remove(id) {
+            return None;
+        }
+        match seal_session(&session, config) {
+            Ok(value) => Some(session_cookie(&value, config)),
+            Err(e) => {
+                eprintln!("[session] cannot seal session {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Start a new, empty session
+    pub fn create(&mut self, config: &SessionConfig) -> io::Result<&mut Session> {
+        let id = create_session_id()?;
+        let now = SystemTime::now();
+        let mut session = Session {
+            id: id.clone(),
+            created: now,
+            last_access: now,
+            expires: now,
+            data: HashMap::new(),
+            saved_access: UNIX_EPOCH,
+        };
+        session.touch(config);
+        self.dirty.insert(id.clone());
+        Ok(self.sessions.entry(id).or_insert(session))
+    }
+
+    /// The live session with this ID, marked as just used. An expired one
+    /// is dropped and not returned.
+    pub fn get(&mut self, id: &str, config: &SessionConfig) -> Option<&mut Session> {
+        if self.sessions.get(id)?.is_expired(SystemTime::now()) {
+            self.remove(id);
+            return None;
+        }
+        let session = self.sessions.get_mut(id)?;
+        session.touch(config);
+
+        let unsaved = session
+            .last_access
+            .duration_since(session.saved_access)
+            .is_ok_and(|d| d.as_secs() >= TOUCH_PERSIST_SECS);
+        if unsaved {
+            self.dirty.insert(id.to_string());
+        }
+        Some(session)
+    }
+
+    /// Store `value` under `key` in a session
+    pub fn set_value(&mut self, id: &str, key: &str, value: &str) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.data.insert(key.to_string(), value.to_string());
+            self.dirty.insert(id.to_string());
+        }
+    }
+
+    /// Drop `key` from a session
+    pub fn remove_value(&mut self, id: &str, key: &str) {
+        if let Some(session) = self.sessions.get_mut(id)
+            && session.data.remove(key).is_some()
+        {
+            self.dirty.insert(id.to_string());
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Session> {
+        let session = self.sessions.remove(id)?;
+        self.dirty.insert(id.to_string());
+        Some(session)
+    }
+
+    /// Drop expired sessions; returns how many went
+    pub fn sweep(&mut self) -> usize {
+        let now = SystemTime::now();
+        let expired: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|session| session.is_expired(now))
+            .map(|session| session.id.clone())
+            .collect();
+        for id in &expired {
+            self.remove(id);
+        }
+        expired.len()
+    }
+
+    /// Write changed sessions to disk and delete removed ones. Each file
+    /// is replaced through a synced temp file and a rename, so a crash
+    /// leaves either the old or the new version, never a torn one.
+    pub fn flush(&mut self) {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => {
+                self.dirty.clear();
+                return;
+            }
+        };
+
+        for id in self.dirty.drain() {
+            let path = dir.join(format!("{}.{}", id, SESSION_EXT));
+            let result = match self.sessions.get_mut(&id) {
+                Some(session) => save_session(session, &path).map(|_| session.saved_access = session.last_access),
+                None => match fs::remove_file(&path) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                },
+            };
+            if let Err(e) = result {
+                eprintln!("[session] cannot persist {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+/// ```text
+/// created=<unix secs>
+/// last_access=<unix secs>
+/// expires=<unix secs>
+/// data.<percent-encoded key>=<percent-encoded value>
+/// ```
+fn session_text(session: &Session) -> String {
+    let mut text = format!(
+        "created={}\nlast_access={}\nexpires={}\n",
+        unix_secs(session.created),
+        unix_secs(session.last_access),
+        unix_secs(session.expires)
+    );
+    for (key, value) in &session.data {
+        text.push_str(&format!("data.{}={}\n", percent_encode_segment(key), percent_encode_segment(value)));
+    }
+    text
+}
+
+fn parse_session_text(id: &str, text: &str) -> Option<Session> {
+    if !is_session_id(id) {
+        return None;
+    }
+
+    let mut times = [None; 3];
+    let mut data = HashMap::new();
+
+    for line in text.lines() {
+        let (key, value) = line.split_once('=')?;
+        match key {
+            "created" => times[0] = Some(from_unix_secs(value.parse().ok()?)?),
+            "last_access" => times[1] = Some(from_unix_secs(value.parse().ok()?)?),
+            "expires" => times[2] = Some(from_unix_secs(value.parse().ok()?)?),
+            _ => {
+                let key = key.strip_prefix("data.")?;
+                data.insert(percent_decode(key), percent_decode(value));
+            }
+        }
+    }
+
+    let [created, last_access, expires] = times;
+    let last_access = last_access?;
+    Some(Session {
+        id: id.to_string(),
+        created: created?,
+        last_access,
+        expires: expires?,
+        data,
+        saved_access: last_access,
+    })
+}
+
+fn save_session(session: &Session, path: &Path) -> io::Result<()> {
+    let temp = path.with_extension("tmp");
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&temp)?;
+    file.write_all(session_text(session).as_bytes())?;
+    file.sync_data()?;
+    fs::rename(&temp, path)
+}
+
+fn load_session(path: &Path) -> Option<Session> {
+    let id = path.file_stem()?.to_str()?;
+    parse_session_text(id, &fs::read_to_string(path).ok()?)
+}
+
+/// Encryption and MAC keys derived from one `session_secret` key
+fn sealing_keys(secret: &str) -> ([u8; 32], [u8; 32]) {
+    (
+        hmac_sha256(secret.as_bytes(), b"localhost session cookie encryption"),
+        hmac_sha256(secret.as_bytes(), b"localhost session cookie authentication"),
+    )
+}
+
+/// The session as a cookie value: base64url of nonce, ChaCha20 ciphertext
+/// and an HMAC-SHA256 tag over both, under the first `session_secret` key
+fn seal_session(session: &Session, config: &SessionConfig) -> Result<String, &'static str> {
+    let secret = config.secrets.first().ok_or("no session_secret")?;
+    let (enc_key, mac_key) = sealing_keys(secret);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    fill_random(&mut nonce).map_err(|_| "no randomness for the nonce")?;
+
+    let mut sealed = nonce.to_vec();
+    let mut payload = format!("{}\n{}", session.id, session_text(session)).into_bytes();
+    chacha20(&enc_key, &nonce, 1, &mut payload);
+    sealed.extend_from_slice(&payload);
+    let tag = hmac_sha256(&mac_key, &sealed);
+    sealed.extend_from_slice(&tag);
+
+    let value = base64url_encode(&sealed);
+    if value.len() > MAX_SEALED_LEN {
+        return Err("too much data for a cookie");
+    }
+    Ok(value)
+}
+
+/// The session sealed in a cookie value, if any accepted key's tag
+/// matches, and whether that key was the current (first) one
+fn open_sealed(value: &str, config: &SessionConfig) -> Option<(Session, bool)> {
+    let sealed = base64url_decode(value)?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (body, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+
+    let (index, (enc_key, _)) = config
+        .secrets
+        .iter()
+        .map(|secret| sealing_keys(secret))
+        .enumerate()
+        .find(|(_, (_, mac_key))| constant_time_eq(&hmac_sha256(mac_key, body), tag))?;
+
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    let mut payload = ciphertext.to_vec();
+    chacha20(&enc_key, nonce.try_into().ok()?, 1, &mut payload);
+
+    let payload = String::from_utf8(payload).ok()?;
+    let (id, text) = payload.split_once('\n')?;
+    Some((parse_session_text(id, text)?, index == 0))
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// None for a time too far out for SystemTime
+fn from_unix_secs(secs: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// `secs` after `time`; a span too long for SystemTime is cut to about
+/// 136 years rather than overflowing
+fn secs_after(time: SystemTime, secs: u64) -> SystemTime {
+    time.checked_add(Duration::from_secs(secs))
+        .or_else(|| time.checked_add(Duration::from_secs(u32::MAX.into())))
+        .unwrap_or(time)
+}
+
+/// A new unguessable session ID: 128 bits from the kernel CSPRNG
+pub fn create_session_id() -> io::Result<String> {
+    random_hex(SESSION_ID_BYTES)
+}
+
+fn is_session_id(value: &str) -> bool {
+    value.len() == SESSION_ID_BYTES * 2 && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// The raw value of the session cookie
+fn cookie_value<'a>(cookie_header: Option<&'a str>, config: &SessionConfig) -> Option<&'a str> {
+    cookie_header?
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == config.cookie_name)
+        .map(|(_, value)| value.trim().trim_matches('"'))
+}
+
+/// The session ID from the cookie named in the config. Values that aren't
+/// shaped like one of our IDs are ignored, and with `session_secret` set
+/// so are IDs without a valid signature from one of its keys.
+pub fn get_session_id(cookie_header: Option<&str>, config: &SessionConfig) -> Option<String> {
+    let value = cookie_value(cookie_header, config)?;
+    let id = match value.split_once('.') {
+        Some((id, signature)) if !config.secrets.is_empty() => {
+            let signature = base64url_decode(signature)?;
+            let valid = config
+                .secrets
+                .iter()
+                .any(|secret| constant_time_eq(&hmac_sha256(secret.as_bytes(), id.as_bytes()), &signature));
+            valid.then_some(id)?
+        }
+        None if config.secrets.is_empty() => value,
+        _ => return None,
+    };
+    is_session_id(id).then(|| id.to_string())
+}
+
+/// `id` as it goes in the cookie: `<id>.<signature>` with the first
+/// `session_secret` key, or bare without one
+fn sign_session_id(id: &str, config: &SessionConfig) -> String {
+    match config.secrets.first() {
+        Some(secret) => format!("{}.{}", id, base64url_encode(&hmac_sha256(secret.as_bytes(), id.as_bytes()))),
+        None => id.to_string(),
     }
-    
-    None
-}
\ No newline at end of file
+}
+
+/// `Set-Cookie` value handing the session cookie `value` to the client
+pub fn session_cookie(value: &str, config: &SessionConfig) -> String {
+    cookie_with_attributes(value, config.max_age, config)
+}
+
+/// `Set-Cookie` value telling the client to drop its session cookie
+pub fn clear_session_cookie(config: &SessionConfig) -> String {
+    cookie_with_attributes("", Some(0), config)
+}
+
+fn cookie_with_attributes(value: &str, max_age: Option<u64>, config: &SessionConfig) -> String {
+    let same_site = match config.same_site {
+        SameSite::Strict => "Strict",
+        SameSite::Lax => "Lax",
+        SameSite::None => "None",
+    };
+    let mut cookie = format!("{}={}; Path=/; HttpOnly; SameSite={}", config.cookie_name, value, same_site);
+    // Browsers reject SameSite=None without Secure
+    if config.secure || config.same_site == SameSite::None {
+        cookie.push_str("; Secure");
+    }
+    if let Some(max_age) = max_age {
+        cookie.push_str(&format!("; Max-Age={}", max_age));
+    }
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SessionBackend;
+
+    const OLD_KEY: &str = "an old session secret, retired but still accepted";
+    const NEW_KEY: &str = "the current session secret, used for new cookies";
+
+    fn config(store: SessionBackend, secrets: &[&str]) -> SessionConfig {
+        SessionConfig {
+            store,
+            secrets: secrets.iter().map(|s| s.to_string()).collect(),
+            ..SessionConfig::default()
+        }
+    }
+
+    /// The cookie value out of a `Set-Cookie` header value
+    fn value_of(set_cookie: &str) -> String {
+        let (pair, _) = set_cookie.split_once(';').unwrap();
+        pair.strip_prefix("SID=").unwrap().to_string()
+    }
+
+    /// A sealed cookie for a new session holding `user=alice`
+    fn sealed_cookie(config: &SessionConfig) -> (String, String) {
+        let mut store = SessionStore::stateless();
+        let id = store.create(config).unwrap().id.clone();
+        store.set_value(&id, "user", "alice");
+        let cookie = store.outgoing_cookie(&id, true, config).expect("a new session sends its cookie");
+        (id, value_of(&cookie))
+    }
+
+    #[test]
+    fn sealed_round_trip() {
+        let config = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let (id, value) = sealed_cookie(&config);
+
+        let (session, current_key) = open_sealed(&value, &config).expect("seal opens");
+        assert!(current_key);
+        assert_eq!(session.id, id);
+        assert_eq!(session.data.get("user").map(String::as_str), Some("alice"));
+
+        // A later request resumes it from the cookie alone
+        let mut store = SessionStore::stateless();
+        let session = store.resume(Some(&format!("theme=dark; SID={}", value)), &config).expect("session resumes");
+        assert_eq!(session.data.get("user").map(String::as_str), Some("alice"));
+        // Unchanged, so no new cookie goes out
+        assert_eq!(store.outgoing_cookie(&id, false, &config), None);
+    }
+
+    #[test]
+    fn removed_value_is_saved() {
+        let config = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let (id, value) = sealed_cookie(&config);
+
+        // Removing a key it doesn't hold leaves the cookie as it is
+        let mut store = SessionStore::stateless();
+        store.resume(Some(&format!("SID={}", value)), &config).expect("session resumes");
+        store.remove_value(&id, "missing");
+        assert_eq!(store.outgoing_cookie(&id, false, &config), None);
+
+        store.resume(Some(&format!("SID={}", value)), &config).expect("session resumes");
+        store.remove_value(&id, "user");
+        let cookie = store.outgoing_cookie(&id, false, &config).expect("a changed session sends its cookie");
+        let (session, _) = open_sealed(&value_of(&cookie), &config).expect("seal opens");
+        assert!(session.data.is_empty());
+    }
+
+    #[test]
+    fn seals_differ_and_hide_data() {
+        let config = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let (_, first) = sealed_cookie(&config);
+        let (_, second) = sealed_cookie(&config);
+        assert_ne!(first, second);
+
+        let raw = base64url_decode(&first).unwrap();
+        assert!(!raw.windows(5).any(|w| w == b"alice"));
+    }
+
+    #[test]
+    fn tampered_seal_is_rejected() {
+        let config = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let (_, value) = sealed_cookie(&config);
+        let raw = base64url_decode(&value).unwrap();
+
+        // A flipped bit in the nonce, the ciphertext or the tag
+        for index in [0, NONCE_LEN + 3, raw.len() - 1] {
+            let mut tampered = raw.clone();
+            tampered[index] ^= 0x01;
+            assert!(open_sealed(&base64url_encode(&tampered), &config).is_none(), "byte {index}");
+        }
+        assert!(open_sealed(&base64url_encode(&raw[..raw.len() - 1]), &config).is_none());
+        assert!(open_sealed(&base64url_encode(&raw[..NONCE_LEN + TAG_LEN - 1]), &config).is_none());
+        assert!(open_sealed("not base64!", &config).is_none());
+        assert!(SessionStore::stateless().resume(Some(&format!("SID={}x", value)), &config).is_none());
+    }
+
+    #[test]
+    fn sealed_key_rotation() {
+        let old = config(SessionBackend::Cookie, &[OLD_KEY]);
+        let rotated = config(SessionBackend::Cookie, &[NEW_KEY, OLD_KEY]);
+        let new_only = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let (id, value) = sealed_cookie(&old);
+
+        let (_, current_key) = open_sealed(&value, &rotated).expect("retired key still opens");
+        assert!(!current_key);
+        assert!(open_sealed(&value, &new_only).is_none());
+
+        // Resuming under a retired key reseals with the current one
+        let mut store = SessionStore::stateless();
+        store.resume(Some(&format!("SID={}", value)), &rotated).expect("session resumes");
+        let resealed = value_of(&store.outgoing_cookie(&id, false, &rotated).expect("cookie is resealed"));
+        let (session, current_key) = open_sealed(&resealed, &new_only).expect("new seal opens with the new key");
+        assert!(current_key);
+        assert_eq!(session.data.get("user").map(String::as_str), Some("alice"));
+    }
+
+    #[test]
+    fn oversized_session_is_not_sealed() {
+        let config = config(SessionBackend::Cookie, &[NEW_KEY]);
+        let mut store = SessionStore::stateless();
+        let id = store.create(&config).unwrap().id.clone();
+        store.set_value(&id, "blob", &"x".repeat(MAX_SEALED_LEN));
+        assert_eq!(store.outgoing_cookie(&id, true, &config), None);
+    }
+
+    #[test]
+    fn signed_id_key_rotation() {
+        let old = config(SessionBackend::Memory, &[OLD_KEY]);
+        let rotated = config(SessionBackend::Memory, &[NEW_KEY, OLD_KEY]);
+        let new_only = config(SessionBackend::Memory, &[NEW_KEY]);
+
+        let mut store = SessionStore::new();
+        let id = store.create(&old).unwrap().id.clone();
+        let value = value_of(&store.outgoing_cookie(&id, true, &old).expect("a new session sends its cookie"));
+        let header = format!("SID={}", value);
+        assert_eq!(get_session_id(Some(&header), &old), Some(id.clone()));
+        assert_eq!(get_session_id(Some(&header), &rotated), Some(id.clone()));
+        assert_eq!(get_session_id(Some(&header), &new_only), None);
+
+        // The retired signature is swapped for a current one
+        assert!(store.resume(Some(&header), &rotated).is_some());
+        let resigned = value_of(&store.outgoing_cookie(&id, false, &rotated).expect("cookie is re-signed"));
+        assert_eq!(get_session_id(Some(&format!("SID={}", resigned)), &new_only), Some(id.clone()));
+        assert_eq!(store.outgoing_cookie(&id, false, &rotated), None);
+    }
+
+    #[test]
+    fn huge_timeouts_do_not_overflow() {
+        let config = SessionConfig { idle_timeout: u64::MAX, max_age: Some(u64::MAX), ..SessionConfig::default() };
+        let mut store = SessionStore::new();
+        let session = store.create(&config).unwrap();
+        assert!(session.expires > SystemTime::now() + Duration::from_secs(100 * 365 * 24 * 60 * 60));
+
+        let id = "0123456789abcdef0123456789abcdef";
+        let text = format!("created=0\nlast_access=0\nexpires={}\n", u64::MAX);
+        assert!(parse_session_text(id, &text).is_none());
+        assert!(parse_session_text(id, "created=0\nlast_access=0\nexpires=60\ndata.a=b\n").is_some());
+    }
+
+    #[test]
+    fn forged_ids_are_rejected() {
+        let signed = config(SessionBackend::Memory, &[NEW_KEY]);
+        let unsigned = config(SessionBackend::Memory, &[]);
+        let id = "0123456789abcdef0123456789abcdef";
+        let value = sign_session_id(id, &signed);
+
+        assert_eq!(get_session_id(Some(&format!("SID={}", value)), &signed), Some(id.to_string()));
+        // Signature of another ID, a bare ID, and a signature made with another key
+        let other = sign_session_id("fedcba9876543210fedcba9876543210", &signed);
+        let (_, other_signature) = other.split_once('.').unwrap();
+        assert_eq!(get_session_id(Some(&format!("SID={}.{}", id, other_signature)), &signed), None);
+        assert_eq!(get_session_id(Some(&format!("SID={}", id)), &signed), None);
+        assert_eq!(get_session_id(Some(&format!("SID={}", sign_session_id(id, &config(SessionBackend::Memory, &[OLD_KEY])))), &signed), None);
+
+        assert_eq!(get_session_id(Some(&format!("SID={}", id)), &unsigned), Some(id.to_string()));
+        assert_eq!(get_session_id(Some(&format!("SID={}", value)), &unsigned), None);
+        assert_eq!(get_session_id(Some("SID=../../etc/passwd"), &unsigned), None);
+    }
+}
@@ -1,5 +1,11 @@
 use std::fs;
 use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::http::accepts_encoding;
+
+/// Precompressed sibling suffixes, in order of preference
+const PRECOMPRESSED: &[(&str, &str)] = &[("br", ".br"), ("gzip", ".gz")];
 
 /// Serve a file from disk
 pub fn serve_file(file_path: &str) -> Result<Vec<u8>, String> {
@@ -14,3 +20,39 @@ pub fn serve_file(file_path: &str) -> Result<Vec<u8>, String> {
         Err(e) => Err(format!("Could not read file: {}", e)),
     }
 }
+
+/// Build an ETag from a file's modification time and size ("mtime-size" in hex)
+pub fn file_etag(metadata: &fs::Metadata) -> String {
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", mtime, metadata.len())
+}
+
+/// Find a `.br` / `.gz` sibling of `file_path` that the client accepts and
+/// that is at least as new as the original. Returns (path, content coding).
+pub fn find_precompressed(file_path: &str, accept_encoding: &str) -> Option<(String, &'static str)> {
+    let original_mtime = fs::metadata(file_path).and_then(|m| m.modified()).ok()?;
+    
+    for (coding, suffix) in PRECOMPRESSED {
+        if !accepts_encoding(accept_encoding, coding) {
+            continue;
+        }
+        
+        let candidate = format!("{}{}", file_path, suffix);
+        let fresh = fs::metadata(&candidate)
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| m.modified().ok())
+            .is_some_and(|mtime| mtime >= original_mtime);
+        
+        if fresh {
+            return Some((candidate, coding));
+        }
+    }
+    
+    None
+}
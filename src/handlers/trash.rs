@@ -0,0 +1,157 @@
+use crate::config::RouteConfig;
+use crate::http::{HttpRequest, HttpResponse};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Deleted files go here, inside the route root, one directory per
+/// deletion named after its unix time
+const TRASH_DIR: &str = ".trash";
+
+/// Give up looking for a free `<time>-N` slot after this many
+const MAX_SLOT_ATTEMPTS: u32 = 1000;
+
+/// Names the server keeps its own state under in a route root: the trash,
+/// resumable upload state and uploads still being received. They are never
+/// served, listed or archived.
+pub fn is_internal_name(name: &str) -> bool {
+    name == TRASH_DIR || name == ".tus" || (name.starts_with(".upload-") && name.ends_with(".part"))
+}
+
+/// `POST <path>?restore`
+pub fn is_restore_request(request: &HttpRequest) -> bool {
+    request.method.eq_ignore_ascii_case("POST")
+        && request
+            .query
+            .split('&')
+            .any(|param| param == "restore" || param.starts_with("restore="))
+}
+
+/// Move `path` (under the route root) to `<root>/.trash/<time>/`, keeping
+/// its path relative to the root so it can be restored
+pub fn move_to_trash(route: &RouteConfig, path: &Path) -> io::Result<()> {
+    let relative = path
+        .strip_prefix(&route.root)
+        .map_err(|_| io::Error::new(io::ErrorKind::PermissionDenied, "outside the route root"))?;
+
+    let now = unix_time();
+    let trash = trash_dir(route);
+
+    for attempt in 0..MAX_SLOT_ATTEMPTS {
+        // The same path deleted twice within a second gets its own slot
+        let slot = match attempt {
+            0 => now.to_string(),
+            n => format!("{}-{}", now, n),
+        };
+        let dest = trash.join(&slot).join(relative);
+        if fs::symlink_metadata(&dest).is_ok() {
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        return fs::rename(path, &dest);
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no free trash slot"))
+}
+
+/// Put the most recently trashed copy of `path` back: 201 if restored,
+/// 404 if the trash has none, 409 if something has taken its place since
+pub fn restore_from_trash(request: &HttpRequest, route: &RouteConfig, path: &Path) -> Result<HttpResponse, u16> {
+    let relative = path.strip_prefix(&route.root).map_err(|_| 403u16)?;
+    if relative.as_os_str().is_empty() {
+        return Err(404);
+    }
+
+    let trash = trash_dir(route);
+    let (slot, source) = trash_slots(&trash)
+        .into_iter()
+        .rev()
+        .map(|(_, slot)| {
+            let source = trash.join(&slot).join(relative);
+            (slot, source)
+        })
+        .find(|(_, source)| fs::symlink_metadata(source).is_ok())
+        .ok_or(404u16)?;
+
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(409);
+    }
+
+    let restored = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::rename(&source, path));
+    if let Err(e) = restored {
+        eprintln!("[trash] failed to restore {}: {}", path.display(), e);
+        return Err(500);
+    }
+
+    // Drop directories the restore left empty, up to and including the slot
+    let slot_dir = trash.join(&slot);
+    let mut dir = source.parent();
+    while let Some(current) = dir {
+        if !current.starts_with(&slot_dir) || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+
+    let mut response = HttpResponse::new(201, "Created");
+    response.set_header("Location", &request.path);
+    Ok(response)
+}
+
+/// Purge trash slots older than the route's `trash_retention`
+pub fn sweep_trash(route: &RouteConfig) {
+    let retention = match route.trash_retention {
+        Some(retention) if route.trash => retention,
+        _ => return,
+    };
+
+    let trash = trash_dir(route);
+    let now = unix_time();
+
+    for (time, slot) in trash_slots(&trash) {
+        if now.saturating_sub(time) <= retention {
+            continue;
+        }
+        match fs::remove_dir_all(trash.join(&slot)) {
+            Ok(()) => eprintln!("[trash] purged {}/{}", trash.display(), slot),
+            Err(e) => eprintln!("[trash] failed to purge {}/{}: {}", trash.display(), slot, e),
+        }
+    }
+}
+
+/// Slot directories in the trash with the time they were created, oldest
+/// first
+fn trash_slots(trash: &Path) -> Vec<(u64, String)> {
+    let mut slots: Vec<(u64, u32, String)> = match fs::read_dir(trash) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let (time, n) = match name.split_once('-') {
+                    Some((time, n)) => (time.parse().ok()?, n.parse().ok()?),
+                    None => (name.parse().ok()?, 0),
+                };
+                Some((time, n, name))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    slots.sort();
+    slots.into_iter().map(|(time, _, name)| (time, name)).collect()
+}
+
+fn trash_dir(route: &RouteConfig) -> PathBuf {
+    Path::new(&route.root).join(TRASH_DIR)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
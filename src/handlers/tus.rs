@@ -0,0 +1,303 @@
+use crate::config::RouteConfig;
+use crate::crypto::{base64_decode, random_hex};
+use crate::http::{status_text, HttpRequest, HttpResponse};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{check_extension, check_quota, store_upload};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination";
+
+/// Per-upload state lives here, inside the route root
+const STATE_DIR: &str = ".tus";
+
+/// Random bytes in an upload id (hex encoded in URLs)
+const ID_BYTES: usize = 16;
+
+/// State of one upload, as persisted in its `.info` file
+struct TusUpload {
+    length: u64,
+    metadata: String,
+    /// Name the completed file was stored under
+    stored: Option<String>,
+}
+
+/// Requests meant for the tus endpoint: clients send `Tus-Resumable` on
+/// everything except OPTIONS discovery
+pub fn is_tus_request(request: &HttpRequest) -> bool {
+    request.header("Tus-Resumable").is_some() || request.method.eq_ignore_ascii_case("OPTIONS")
+}
+
+/// Handle a tus request (core protocol plus the creation and termination
+/// extensions) on a route with `tus = on`. `max_size` caps the total size
+/// of each upload. Each upload keeps its data and a small info file under
+/// `<root>/.tus/`, so it can be resumed after a server restart.
+pub fn handle_tus(request: &HttpRequest, route: &RouteConfig, max_size: u64) -> HttpResponse {
+    let mut response = match dispatch(request, route, max_size) {
+        Ok(response) => response,
+        Err(code) => tus_error(code),
+    };
+    response.set_header("Tus-Resumable", TUS_VERSION);
+    response.set_header("Cache-Control", "no-store");
+    response
+}
+
+/// The discovery headers of a tus OPTIONS response, for when another
+/// handler (WebDAV) answers OPTIONS on the same route
+pub fn add_tus_options(response: &mut HttpResponse, max_size: u64) {
+    response.set_header("Tus-Resumable", TUS_VERSION);
+    response.set_header("Tus-Version", TUS_VERSION);
+    response.set_header("Tus-Extension", TUS_EXTENSIONS);
+    response.set_header("Tus-Max-Size", &max_size.to_string());
+}
+
+fn dispatch(request: &HttpRequest, route: &RouteConfig, max_size: u64) -> Result<HttpResponse, u16> {
+    let method = request.method.to_ascii_uppercase();
+
+    if method == "OPTIONS" {
+        let mut response = HttpResponse::new(204, "No Content");
+        add_tus_options(&mut response, max_size);
+        return Ok(response);
+    }
+
+    if request.header("Tus-Resumable") != Some(TUS_VERSION) {
+        return Err(412);
+    }
+
+    let id = request
+        .path
+        .strip_prefix(&route.path)
+        .unwrap_or("")
+        .trim_matches('/');
+
+    if id.is_empty() {
+        return match method.as_str() {
+            "POST" => create(request, route, max_size),
+            _ => Err(405),
+        };
+    }
+
+    // Ids are ours; anything else can't name an upload (and keeps the
+    // path from reaching outside the state directory)
+    if id.len() != ID_BYTES * 2 || !id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return Err(404);
+    }
+
+    match method.as_str() {
+        "HEAD" => offset(route, id),
+        "PATCH" => append(request, route, id),
+        "DELETE" => terminate(route, id),
+        _ => Err(405),
+    }
+}
+
+/// POST: announce a new upload of `Upload-Length` bytes
+fn create(request: &HttpRequest, route: &RouteConfig, max_size: u64) -> Result<HttpResponse, u16> {
+    let length: u64 = request
+        .header("Upload-Length")
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or(400u16)?;
+    if length > max_size {
+        return Err(413);
+    }
+
+    let metadata = request.header("Upload-Metadata").unwrap_or("").trim().to_string();
+    if parse_metadata(&metadata).is_none() {
+        return Err(400);
+    }
+
+    let id = random_hex(ID_BYTES).map_err(|_| 500u16)?;
+    let mut upload = TusUpload { length, metadata, stored: None };
+
+    // Refuse now what would be refused on completion, before the client
+    // sends any of it (content sniffing has to wait for the data)
+    check_extension(route, &upload_filename(&upload, &id))?;
+    check_quota(route, length, None)?;
+
+    let created = fs::create_dir_all(state_dir(route))
+        .and_then(|_| OpenOptions::new().write(true).create_new(true).open(data_path(route, &id)))
+        .and_then(|_| save_info(route, &id, &upload));
+    if let Err(e) = created {
+        eprintln!("[tus] cannot create upload: {}", e);
+        return Err(500);
+    }
+
+    // Nothing to wait for
+    if length == 0 {
+        complete(route, &id, &mut upload)?;
+    }
+
+    let mut response = HttpResponse::new(201, "Created");
+    response.set_header("Location", &format!("{}/{}", route.path.trim_end_matches('/'), id));
+    Ok(response)
+}
+
+/// HEAD: how much of the upload the server has
+fn offset(route: &RouteConfig, id: &str) -> Result<HttpResponse, u16> {
+    let upload = load_info(route, id).ok_or(404u16)?;
+    let offset = current_offset(route, id, &upload);
+
+    let mut response = HttpResponse::ok();
+    response.set_header("Upload-Offset", &offset.to_string());
+    response.set_header("Upload-Length", &upload.length.to_string());
+    if !upload.metadata.is_empty() {
+        response.set_header("Upload-Metadata", &upload.metadata);
+    }
+    Ok(response)
+}
+
+/// PATCH: append the body at `Upload-Offset`
+fn append(request: &HttpRequest, route: &RouteConfig, id: &str) -> Result<HttpResponse, u16> {
+    let content_type = request.header("Content-Type").unwrap_or("");
+    if !content_type.trim().eq_ignore_ascii_case("application/offset+octet-stream") {
+        return Err(415);
+    }
+
+    let claimed: u64 = request
+        .header("Upload-Offset")
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or(400u16)?;
+
+    let mut upload = load_info(route, id).ok_or(404u16)?;
+    let offset = current_offset(route, id, &upload);
+    if upload.stored.is_some() || claimed != offset {
+        return Err(409);
+    }
+    if offset + request.body_len() > upload.length {
+        return Err(413);
+    }
+
+    if let Err(e) = append_body(request, &data_path(route, id)) {
+        eprintln!("[tus] append to {} failed: {}", id, e);
+        return Err(500);
+    }
+
+    let offset = offset + request.body_len();
+    if offset == upload.length {
+        complete(route, id, &mut upload)?;
+    }
+
+    let mut response = HttpResponse::new(204, "No Content");
+    response.set_header("Upload-Offset", &offset.to_string());
+    Ok(response)
+}
+
+/// DELETE: abandon an upload and free its space
+fn terminate(route: &RouteConfig, id: &str) -> Result<HttpResponse, u16> {
+    if load_info(route, id).is_none() {
+        return Err(404);
+    }
+    let _ = fs::remove_file(data_path(route, id));
+    let _ = fs::remove_file(info_path(route, id));
+    Ok(HttpResponse::new(204, "No Content"))
+}
+
+/// Move the finished data to its name from the metadata. The info file
+/// stays behind so a client asking for the offset still sees it complete.
+fn complete(route: &RouteConfig, id: &str, upload: &mut TusUpload) -> Result<(), u16> {
+    let stored = store_upload(&data_path(route, id), route, &upload_filename(upload, id))?;
+    upload.stored = Some(stored);
+    save_info(route, id, upload).map_err(|_| 500u16)
+}
+
+/// The name the client gave in the metadata, if any
+fn upload_filename(upload: &TusUpload, id: &str) -> String {
+    parse_metadata(&upload.metadata)
+        .unwrap_or_default()
+        .iter()
+        .find(|(key, _)| key == "filename" || key == "name")
+        .map(|(_, value)| String::from_utf8_lossy(value).to_string())
+        .unwrap_or_else(|| format!("{}.bin", id))
+}
+
+fn append_body(request: &HttpRequest, path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    match &request.body_file {
+        Some(body_file) => {
+            io::copy(&mut File::open(&body_file.path)?, &mut file)?;
+        }
+        None => file.write_all(&request.body)?,
+    }
+    // The offset we report must survive a crash
+    file.sync_data()
+}
+
+fn current_offset(route: &RouteConfig, id: &str, upload: &TusUpload) -> u64 {
+    if upload.stored.is_some() {
+        return upload.length;
+    }
+    fs::metadata(data_path(route, id)).map(|m| m.len()).unwrap_or(0)
+}
+
+/// `key base64value,key2 base64value2,key3`; None if a value isn't base64
+fn parse_metadata(header: &str) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut pairs = Vec::new();
+    for item in header.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+        match item.split_once(' ') {
+            Some((key, value)) => pairs.push((key.to_string(), base64_decode(value.trim())?)),
+            None => pairs.push((item.to_string(), Vec::new())),
+        }
+    }
+    Some(pairs)
+}
+
+fn state_dir(route: &RouteConfig) -> PathBuf {
+    Path::new(&route.root).join(STATE_DIR)
+}
+
+fn data_path(route: &RouteConfig, id: &str) -> PathBuf {
+    state_dir(route).join(format!("{}.bin", id))
+}
+
+fn info_path(route: &RouteConfig, id: &str) -> PathBuf {
+    state_dir(route).join(format!("{}.info", id))
+}
+
+fn load_info(route: &RouteConfig, id: &str) -> Option<TusUpload> {
+    let text = fs::read_to_string(info_path(route, id)).ok()?;
+    let mut upload = TusUpload { length: 0, metadata: String::new(), stored: None };
+    let mut has_length = false;
+
+    for line in text.lines() {
+        match line.split_once('=') {
+            Some(("length", value)) => {
+                upload.length = value.parse().ok()?;
+                has_length = true;
+            }
+            Some(("metadata", value)) => upload.metadata = value.to_string(),
+            Some(("stored", value)) => upload.stored = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    has_length.then_some(upload)
+}
+
+/// Write the info file through a temp file and rename, so a crash never
+/// leaves it half written
+fn save_info(route: &RouteConfig, id: &str, upload: &TusUpload) -> io::Result<()> {
+    let mut text = format!("length={}\nmetadata={}\n", upload.length, upload.metadata);
+    if let Some(stored) = &upload.stored {
+        text.push_str(&format!("stored={}\n", stored));
+    }
+
+    let path = info_path(route, id);
+    let temp = path.with_extension("info.tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_data()?;
+    fs::rename(&temp, &path)
+}
+
+/// Plain-text errors: tus clients are programs, not browsers
+fn tus_error(code: u16) -> HttpResponse {
+    let message = status_text(code);
+    let mut response = HttpResponse::new(code, message);
+    response.set_header("Content-Type", "text/plain; charset=utf-8");
+    if code == 412 {
+        response.set_header("Tus-Version", TUS_VERSION);
+    }
+    response.set_body(&format!("{} {}\n", code, message));
+    response
+}
@@ -1,19 +1,118 @@
-use crate::http::{HttpRequest, HttpResponse};
-use std::fs::{self, File};
-use std::io::Write;
+use crate::config::{RouteConfig, UploadConflict};
+use crate::crypto::{hex, Sha256};
+use crate::http::{json_string, multipart_boundary, percent_encode_segment, status_text, HttpRequest, HttpResponse, MultipartError, MultipartReader, Part};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn upload_file(request: &HttpRequest, max_size: usize, error_path: &str) -> HttpResponse {
-    if request.body.len() > max_size {
+use super::check_upload;
+
+/// Form fields are kept in memory; anything larger must be sent as a file
+const MAX_FIELD_SIZE: usize = 64 * 1024;
+
+/// Give up on `upload_conflict = rename` after this many numbered names
+const MAX_RENAME_ATTEMPTS: u32 = 1000;
+
+/// Longest stored name, leaving room for a `-N` suffix within NAME_MAX
+const MAX_NAME_LEN: usize = 200;
+
+/// Longest extension kept, dot included; longer ones are cut
+const MAX_EXT_LEN: usize = 32;
+
+/// Distinguishes temp files created within the same nanosecond
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Non-ASCII letters and their closest ASCII spelling, for stored names
+const TRANSLITERATIONS: &[(&str, &str)] = &[
+    ("ÀÁÂÃÄÅĀĂĄ", "A"), ("àáâãäåāăą", "a"), ("Æ", "AE"), ("æ", "ae"),
+    ("ÇĆĈĊČ", "C"), ("çćĉċč", "c"), ("ĎĐÐ", "D"), ("ďđð", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"), ("èéêëēĕėęě", "e"), ("ĜĞĠĢ", "G"), ("ĝğġģ", "g"),
+    ("ĤĦ", "H"), ("ĥħ", "h"), ("ÌÍÎÏĨĪĬĮİ", "I"), ("ìíîïĩīĭįı", "i"),
+    ("Ĵ", "J"), ("ĵ", "j"), ("Ķ", "K"), ("ķ", "k"),
+    ("ĹĻĽĿŁ", "L"), ("ĺļľŀł", "l"), ("ÑŃŅŇ", "N"), ("ñńņňŉ", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"), ("òóôõöøōŏő", "o"), ("Œ", "OE"), ("œ", "oe"),
+    ("ŔŖŘ", "R"), ("ŕŗř", "r"), ("ŚŜŞŠ", "S"), ("śŝşš", "s"), ("ß", "ss"),
+    ("ŢŤŦ", "T"), ("ţťŧ", "t"), ("Þ", "TH"), ("þ", "th"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"), ("ùúûüũūŭůűų", "u"), ("Ŵ", "W"), ("ŵ", "w"),
+    ("ÝŸŶ", "Y"), ("ýÿŷ", "y"), ("ŹŻŽ", "Z"), ("źżž", "z"),
+];
+
+/// Where uploads go, under which restrictions and how name clashes are
+/// handled
+struct Target<'a> {
+    route: &'a RouteConfig,
+    /// URL prefix under which stored files can be downloaded
+    url_base: &'a str,
+}
+
+impl Target<'_> {
+    fn location(&self, stored: &str) -> String {
+        format!("{}/{}", self.url_base.trim_end_matches('/'), percent_encode_segment(stored))
+    }
+}
+
+/// A file that was written to the upload directory
+struct StoredFile {
+    field: String,
+    filename: String,
+    stored: String,
+    size: u64,
+    sha256: [u8; 32],
+    content_type: String,
+}
+
+enum UploadError {
+    /// Name taken and `upload_conflict = reject`
+    Conflict,
+    /// Refused by the route's upload restrictions, with this status
+    Rejected(u16),
+    FieldTooLarge,
+    Multipart(MultipartError),
+    Io(io::Error),
+}
+
+impl From<MultipartError> for UploadError {
+    fn from(e: MultipartError) -> Self {
+        UploadError::Multipart(e)
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(e: io::Error) -> Self {
+        UploadError::Io(e)
+    }
+}
+
+/// Store an upload under the route's root. `url_base` is where the stored
+/// files can be fetched from, for the Location header.
+pub fn upload_file(request: &HttpRequest, route: &RouteConfig, url_base: &str, max_size: usize, error_path: &str) -> HttpResponse {
+    if request.body_len() > max_size as u64 {
         return error_response(413, error_path, "Payload Too Large");
     }
     
+    let target = Target { route, url_base };
+    
     let ct = request.headers
         .get("Content-Type")
         .map(|v| v.as_str())
         .unwrap_or("");
     
     if ct.to_ascii_lowercase().starts_with("multipart/form-data") {
-        return handle_multipart(ct, &request.body, max_size, error_path);
+        let boundary = match multipart_boundary(ct) {
+            Some(b) => b,
+            None => return error_response(400, error_path, "Bad Request"),
+        };
+        // Parts are read straight from the spooled body when there is one
+        let source: Box<dyn Read> = match &request.body_file {
+            Some(file) => match File::open(&file.path) {
+                Ok(f) => Box::new(f),
+                Err(_) => return error_response(500, error_path, "Internal Server Error"),
+            },
+            None => Box::new(Cursor::new(&request.body[..])),
+        };
+        return handle_multipart(MultipartReader::new(source, &boundary), &target, error_path);
     }
     
     let filename = request.headers
@@ -21,69 +120,286 @@ pub fn upload_file(request: &HttpRequest, max_size: usize, error_path: &str) ->
         .map(|v| v.to_string())
         .unwrap_or_else(|| format!("upload-{}.bin", timestamp_ms()));
     
-    save_file("uploads", &filename, &request.body, error_path)
+    match store_raw(request, &target, &filename) {
+        Ok(file) => {
+            let mut response = HttpResponse::new(201, "Created");
+            response.set_header("Location", &target.location(&file.stored));
+            response.set_header("Content-Type", "application/json");
+            response.set_body(&format!("{}\n", file_json(&file, &target)));
+            response
+        }
+        Err(e) => upload_error(e, error_path),
+    }
 }
 
-fn handle_multipart(ct: &str, body: &[u8], max_size: usize, error_path: &str) -> HttpResponse {
-    let boundary = match extract_boundary(ct) {
-        Some(b) => b,
-        None => return error_response(400, error_path, "Bad Request"),
+/// Store a non-multipart body under the name from X-Filename
+fn store_raw(request: &HttpRequest, target: &Target, filename: &str) -> Result<StoredFile, UploadError> {
+    let (temp_path, size, sha256) = match &request.body_file {
+        // Already on disk in the upload directory
+        Some(file) => (file.path.clone(), file.len, file.sha256),
+        None => {
+            let (temp_path, mut file) = create_upload_temp(&target.route.root)?;
+            if let Err(e) = file.write_all(&request.body) {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e.into());
+            }
+            let mut hasher = Sha256::new();
+            hasher.update(&request.body);
+            (temp_path, request.body.len() as u64, hasher.finish())
+        }
     };
     
-    let delim = format!("--{}", boundary).into_bytes();
+    let stored = place_file(&temp_path, target, &stored_name(filename));
+    if stored.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    
+    Ok(StoredFile {
+        field: String::new(),
+        filename: filename.to_string(),
+        stored: stored?,
+        size,
+        sha256,
+        content_type: request.header("Content-Type").unwrap_or("application/octet-stream").to_string(),
+    })
+}
+
+fn handle_multipart<R: Read>(mut reader: MultipartReader<R>, target: &Target, error_path: &str) -> HttpResponse {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut files: Vec<StoredFile> = Vec::new();
     
-    // Find header end
-    let mut pos = 0;
-    for i in 0..body.len().saturating_sub(3) {
-        if &body[i..i+4] == b"\r\n\r\n" {
-            pos = i + 4;
-            break;
+    if let Err(e) = read_parts(&mut reader, target, &mut fields, &mut files) {
+        // Don't leave half a form behind
+        for file in &files {
+            let _ = fs::remove_file(Path::new(&target.route.root).join(&file.stored));
         }
+        return upload_error(e, error_path);
     }
     
-    // Extract filename
-    let headers = String::from_utf8_lossy(&body[0..pos]);
-    let filename = headers
-        .split("filename=\"")
-        .nth(1)
-        .and_then(|s| s.split('"').next())
-        .unwrap_or("upload.bin")
-        .to_string();
-    
-    // Find next boundary
-    let data_end = body[pos..]
-        .windows(delim.len())
-        .position(|w| w == &delim[..])
-        .map(|i| pos + i - 2)
-        .unwrap_or(body.len());
+    if fields.is_empty() && files.is_empty() {
+        return error_response(400, error_path, "Bad Request");
+    }
     
-    let file_data = &body[pos..data_end];
+    // Created only if a file was actually stored; a form with plain
+    // fields is just acknowledged
+    let mut response = match files.first() {
+        Some(first) => {
+            let mut response = HttpResponse::new(201, "Created");
+            response.set_header("Location", &target.location(&first.stored));
+            response
+        }
+        None => HttpResponse::ok(),
+    };
+    response.set_header("Content-Type", "application/json");
+    response.set_body(&multipart_summary(&fields, &files, target));
+    response
+}
+
+fn read_parts<R: Read>(
+    reader: &mut MultipartReader<R>,
+    target: &Target,
+    fields: &mut Vec<(String, String)>,
+    files: &mut Vec<StoredFile>,
+) -> Result<(), UploadError> {
+    while let Some(part) = reader.next_part()? {
+        // An empty file input is sent as a part with filename=""
+        if part.filename.as_deref() == Some("") {
+            continue;
+        }
     
-    if file_data.len() > max_size {
-        return error_response(413, error_path, "Payload Too Large");
+        if is_file_part(&part) {
+            files.push(store_part(reader, &part, target)?);
+        } else {
+            let mut value = Vec::new();
+            let mut too_large = false;
+            let result = reader.read_body(|data| {
+                if value.len() + data.len() > MAX_FIELD_SIZE {
+                    too_large = true;
+                    return Err(io::Error::other("form field too large"));
+                }
+                value.extend_from_slice(data);
+                Ok(())
+            });
+            if too_large {
+                return Err(UploadError::FieldTooLarge);
+            }
+            result?;
+            fields.push((part.name, String::from_utf8_lossy(&value).to_string()));
+        }
     }
+    Ok(())
+}
+
+/// Parts with a filename are files, and so is anything that declares a
+/// non-text Content-Type
+fn is_file_part(part: &Part) -> bool {
+    part.filename.is_some()
+        || part
+            .content_type
+            .as_deref()
+            .is_some_and(|ct| !ct.trim().to_ascii_lowercase().starts_with("text/"))
+}
+
+/// Stream one file part into a temp file, then move it into place
+fn store_part<R: Read>(reader: &mut MultipartReader<R>, part: &Part, target: &Target) -> Result<StoredFile, UploadError> {
+    let (temp_path, file) = create_upload_temp(&target.route.root)?;
+    let mut out = BufWriter::new(file);
+    let mut hasher = Sha256::new();
     
-    save_file("uploads", &filename, file_data, error_path)
+    let written = reader
+        .read_body(|data| {
+            hasher.update(data);
+            out.write_all(data)
+        })
+        .map_err(UploadError::from)
+        .and_then(|size| out.flush().map(|_| size).map_err(UploadError::from));
+    
+    let filename = part.filename.clone().unwrap_or_else(|| part.name.clone());
+    let result = written.and_then(|size| {
+        place_file(&temp_path, target, &stored_name(&filename)).map(|stored| (stored, size))
+    });
+    
+    match result {
+        Ok((stored, size)) => Ok(StoredFile {
+            field: part.name.clone(),
+            filename,
+            stored,
+            size,
+            sha256: hasher.finish(),
+            content_type: part.content_type.clone().unwrap_or_else(|| "application/octet-stream".to_string()),
+        }),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
 }
 
-fn extract_boundary(ct: &str) -> Option<String> {
-    ct.split(';')
-        .find(|p| p.trim().starts_with("boundary="))
-        .map(|p| p.split('=').nth(1).unwrap_or("").trim().trim_matches('"').to_string())
+/// Move a completed upload that already sits in the route's root (e.g. a
+/// resumable one) to its final name under the route's conflict policy.
+/// Returns the stored name, or the status to fail with.
+pub fn store_upload(temp_path: &Path, route: &RouteConfig, filename: &str) -> Result<String, u16> {
+    let target = Target { route, url_base: &route.path };
+    
+    match place_file(temp_path, &target, &stored_name(filename)) {
+        Ok(stored) => Ok(stored),
+        Err(UploadError::Conflict) => Err(409),
+        Err(UploadError::Rejected(code)) => Err(code),
+        Err(_) => Err(500),
+    }
 }
 
-fn save_file(dir: &str, filename: &str, data: &[u8], error_path: &str) -> HttpResponse {
-    let _ = fs::create_dir_all(dir);
+/// Move a finished temp file to `name` in the upload directory according
+/// to the route's restrictions and conflict policy. Returns the name it
+/// was stored under.
+fn place_file(temp_path: &Path, target: &Target, name: &str) -> Result<String, UploadError> {
+    let dir = Path::new(&target.route.root);
+    let conflict = target.route.upload_conflict;
     
-    let safe = sanitize_filename(filename);
-    let path = format!("{}/{}", dir, safe);
+    check_upload(target.route, &dir.join(name), temp_path, conflict == UploadConflict::Overwrite)
+        .map_err(UploadError::Rejected)?;
     
-    match File::create(&path).and_then(|mut f| f.write_all(data)) {
-        Ok(_) => HttpResponse::ok_with_message(&format!("File '{}' uploaded successfully", safe)),
-        Err(_) => error_response(500, error_path, "Internal Server Error"),
+    match conflict {
+        UploadConflict::Overwrite => {
+            fs::rename(temp_path, dir.join(name))?;
+            Ok(name.to_string())
+        }
+        UploadConflict::Reject => match link_new(temp_path, &dir.join(name)) {
+            Ok(()) => Ok(name.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(UploadError::Conflict),
+            Err(e) => Err(e.into()),
+        },
+        UploadConflict::Rename => {
+            for attempt in 0..MAX_RENAME_ATTEMPTS {
+                let candidate = numbered_name(name, attempt);
+                match link_new(temp_path, &dir.join(&candidate)) {
+                    Ok(()) => return Ok(candidate),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Err(UploadError::Conflict)
+        }
+    }
+}
+
+/// Give `temp_path` the name `dest`, failing with AlreadyExists instead of
+/// replacing a file that is there (rename would silently overwrite it)
+fn link_new(temp_path: &Path, dest: &Path) -> io::Result<()> {
+    fs::hard_link(temp_path, dest)?;
+    let _ = fs::remove_file(temp_path);
+    Ok(())
+}
+
+/// `report.pdf`, `report-1.pdf`, `report-2.pdf`, ...
+fn numbered_name(name: &str, n: u32) -> String {
+    if n == 0 {
+        return name.to_string();
+    }
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}-{}.{}", stem, n, ext),
+        _ => format!("{}-{}", name, n),
+    }
+}
+
+fn upload_error(e: UploadError, error_path: &str) -> HttpResponse {
+    match e {
+        UploadError::Conflict => error_response(409, error_path, "Conflict"),
+        UploadError::Rejected(code) => error_response(code, error_path, status_text(code)),
+        UploadError::FieldTooLarge => error_response(413, error_path, "Payload Too Large"),
+        UploadError::Multipart(MultipartError::Io(e)) | UploadError::Io(e) => {
+            eprintln!("[upload] failed to store file: {}", e);
+            error_response(500, error_path, "Internal Server Error")
+        }
+        UploadError::Multipart(_) => error_response(400, error_path, "Bad Request"),
     }
 }
 
+fn file_json(file: &StoredFile, target: &Target) -> String {
+    format!(
+        "{{\"name\":{},\"filename\":{},\"size\":{},\"sha256\":\"{}\",\"content_type\":{},\"location\":{}}}",
+        json_string(&file.stored),
+        json_string(&file.filename),
+        file.size,
+        hex(&file.sha256),
+        json_string(&file.content_type),
+        json_string(&target.location(&file.stored))
+    )
+}
+
+fn multipart_summary(fields: &[(String, String)], files: &[StoredFile], target: &Target) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{{\"name\":{},\"value\":{}}}", json_string(name), json_string(value)))
+        .collect();
+    let files: Vec<String> = files
+        .iter()
+        .map(|f| {
+            // Same shape as a single upload's body, plus the form field
+            let json = file_json(f, target);
+            format!("{{\"field\":{},{}", json_string(&f.field), &json[1..])
+        })
+        .collect();
+    format!("{{\"fields\":[{}],\"files\":[{}]}}\n", fields.join(","), files.join(","))
+}
+
+/// Create an empty, uniquely named temp file in `dir` for an upload in
+/// progress. Dot-prefixed, so it never collides with a stored name.
+pub fn create_upload_temp(dir: &str) -> io::Result<(PathBuf, File)> {
+    fs::create_dir_all(dir)?;
+    
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let seq = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = PathBuf::from(dir).join(format!(".upload-{}-{}.part", nanos, seq));
+    
+    // create_new: never clobber an existing file
+    let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    Ok((path, file))
+}
+
 fn error_response(code: u16, error_path: &str, message: &str) -> HttpResponse {
     let error_file = format!("{}/{}.html", error_path, code);
     
@@ -91,6 +407,7 @@ fn error_response(code: u16, error_path: &str, message: &str) -> HttpResponse {
         Ok(content) => {
             let mut response = HttpResponse::new(code, message);
             response.set_header("Content-Type", "text/html");
+            response.set_header("Cache-Control", "no-store");
             response.set_body_bytes(content);
             response
         }
@@ -98,20 +415,52 @@ fn error_response(code: u16, error_path: &str, message: &str) -> HttpResponse {
             // Fallback if error page not found
             let mut response = HttpResponse::new(code, message);
             response.set_header("Content-Type", "text/html");
+            response.set_header("Cache-Control", "no-store");
             response.set_body(&format!("<!DOCTYPE html><html><body><h1>{} - {}</h1></body></html>", code, message));
             response
         }
     }
 }
 
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-' || *c == '_')
-        .collect()
+/// Name a file is stored under: accented letters are transliterated,
+/// anything else outside `[A-Za-z0-9._-]` becomes `_`. Never hidden,
+/// never empty, and always safe in a URL path segment.
+fn stored_name(filename: &str) -> String {
+    let mut safe = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+            safe.push(c);
+        } else if let Some(ascii) = transliterate(c) {
+            safe.push_str(ascii);
+        } else if !safe.ends_with('_') {
+            safe.push('_');
+        }
+    }
+    
+    let safe = safe.trim_start_matches('.');
+    let stem_end = safe.rfind('.').unwrap_or(safe.len());
+    let (stem, ext) = safe.split_at(stem_end);
+    // Everything left is ASCII, so byte slicing is safe
+    let ext = &ext[..ext.len().min(MAX_EXT_LEN)];
+    
+    // Nothing recognizable left (e.g. a name in a non-Latin script):
+    // keep the extension, make up the rest
+    if !stem.chars().any(|c| c.is_ascii_alphanumeric()) {
+        return format!("upload-{}{}", timestamp_ms(), ext);
+    }
+    
+    let keep = MAX_NAME_LEN - ext.len();
+    format!("{}{}", &stem[..keep.min(stem.len())], ext)
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    TRANSLITERATIONS
+        .iter()
+        .find(|(from, _)| from.contains(c))
+        .map(|(_, to)| *to)
 }
 
 fn timestamp_ms() -> u128 {
-    use std::time::{SystemTime, UNIX_EPOCH};
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -0,0 +1,172 @@
+use crate::config::RouteConfig;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// How much of a file is read for magic-number sniffing (enough for the
+/// tar header at offset 257)
+const SNIFF_LEN: usize = 512;
+
+/// A signature: these bytes at this offset
+type Magic = (usize, &'static [u8]);
+
+/// Extensions and the signatures their content must start with (any one)
+const SIGNATURES: &[(&[&str], &[Magic])] = &[
+    (&["png"], &[(0, b"\x89PNG\r\n\x1a\n")]),
+    (&["jpg", "jpeg", "jpe"], &[(0, b"\xff\xd8\xff")]),
+    (&["gif"], &[(0, b"GIF87a"), (0, b"GIF89a")]),
+    (&["bmp"], &[(0, b"BM")]),
+    (&["ico"], &[(0, b"\x00\x00\x01\x00")]),
+    (&["tif", "tiff"], &[(0, b"II*\x00"), (0, b"MM\x00*")]),
+    (&["webp"], &[(8, b"WEBP")]),
+    (&["pdf"], &[(0, b"%PDF-")]),
+    (&["zip", "jar", "apk", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub"], &[(0, b"PK\x03\x04"), (0, b"PK\x05\x06")]),
+    (&["gz", "tgz"], &[(0, b"\x1f\x8b")]),
+    (&["bz2"], &[(0, b"BZh")]),
+    (&["xz"], &[(0, b"\xfd7zXZ\x00")]),
+    (&["7z"], &[(0, b"7z\xbc\xaf\x27\x1c")]),
+    (&["tar"], &[(257, b"ustar")]),
+    (&["mp3"], &[(0, b"ID3"), (0, b"\xff\xfb"), (0, b"\xff\xf3"), (0, b"\xff\xf2")]),
+    (&["mp4", "m4a", "m4v", "mov"], &[(4, b"ftyp")]),
+    (&["wav"], &[(8, b"WAVE")]),
+    (&["avi"], &[(8, b"AVI ")]),
+    (&["ogg", "oga", "ogv"], &[(0, b"OggS")]),
+    (&["flac"], &[(0, b"fLaC")]),
+    (&["webm", "mkv"], &[(0, b"\x1a\x45\xdf\xa3")]),
+    (&["woff"], &[(0, b"wOFF")]),
+    (&["woff2"], &[(0, b"wOF2")]),
+    (&["wasm"], &[(0, b"\x00asm")]),
+    (&["exe", "dll"], &[(0, b"MZ")]),
+];
+
+/// Extensions whose content must be text
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "text", "csv", "tsv", "md", "json", "xml", "html", "htm", "css", "js", "svg", "yaml", "yml", "toml", "ini", "log",
+];
+
+/// Check a file waiting at `temp_path` to become `dest` against the
+/// route's upload restrictions. `overwrite` says whether it replaces a file
+/// already at `dest` (which then no longer counts towards the quota).
+/// Returns the status to refuse with: 415 or 507.
+pub fn check_upload(route: &RouteConfig, dest: &Path, temp_path: &Path, overwrite: bool) -> Result<(), u16> {
+    let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    check_extension(route, &name)?;
+
+    let size = fs::metadata(temp_path).map(|m| m.len()).map_err(|_| 500u16)?;
+    let replaced = if overwrite {
+        fs::metadata(dest).ok().filter(|m| m.is_file()).map(|m| m.len())
+    } else {
+        None
+    };
+    check_quota(route, size, replaced)?;
+
+    if route.upload_sniff && !content_matches_extension(&name, temp_path) {
+        return Err(415);
+    }
+    Ok(())
+}
+
+/// `upload_allow_ext` / `upload_deny_ext` against the name's extension
+pub fn check_extension(route: &RouteConfig, name: &str) -> Result<(), u16> {
+    let ext = extension(name);
+
+    if !route.upload_allow_ext.is_empty() && !route.upload_allow_ext.contains(&ext) {
+        return Err(415);
+    }
+    if route.upload_deny_ext.contains(&ext) {
+        return Err(415);
+    }
+    Ok(())
+}
+
+/// `upload_dir_quota` and `upload_max_files` for `incoming` more bytes in
+/// one more file, or in place of a `replaced` file of that size
+pub fn check_quota(route: &RouteConfig, incoming: u64, replaced: Option<u64>) -> Result<(), u16> {
+    if route.upload_dir_quota.is_none() && route.upload_max_files.is_none() {
+        return Ok(());
+    }
+
+    let (mut bytes, mut files) = (0u64, 0usize);
+    directory_usage(Path::new(&route.root), &mut bytes, &mut files);
+
+    let (bytes, files) = match replaced {
+        Some(old) => (bytes.saturating_sub(old) + incoming, files),
+        None => (bytes + incoming, files + 1),
+    };
+
+    if route.upload_dir_quota.is_some_and(|quota| bytes > quota)
+        || route.upload_max_files.is_some_and(|max| files > max)
+    {
+        return Err(507);
+    }
+    Ok(())
+}
+
+/// Total size and number of regular files under `dir`. Symlinks are not
+/// followed, and dot-entries (in-progress temp files, resumable upload
+/// state) are the server's own bookkeeping rather than stored files.
+fn directory_usage(dir: &Path, bytes: &mut u64, files: &mut usize) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().as_encoded_bytes().starts_with(b".") {
+            continue;
+        }
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => directory_usage(&entry.path(), bytes, files),
+            Ok(meta) if meta.is_file() => {
+                *bytes += meta.len();
+                *files += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether the first bytes of the file fit its extension. Extensions we
+/// know nothing about always pass.
+fn content_matches_extension(name: &str, path: &Path) -> bool {
+    let ext = extension(name);
+
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(SNIFF_LEN as u64).read_to_end(&mut head);
+    }
+    // Nothing to sniff
+    if head.is_empty() {
+        return true;
+    }
+
+    if TEXT_EXTENSIONS.contains(&ext.as_str()) {
+        return looks_like_text(&head);
+    }
+
+    match SIGNATURES.iter().find(|(exts, _)| exts.contains(&ext.as_str())) {
+        Some((_, magics)) => magics
+            .iter()
+            .any(|(offset, magic)| head.get(*offset..offset + magic.len()) == Some(*magic)),
+        None => true,
+    }
+}
+
+/// No NUL bytes and valid UTF-8, allowing a character cut off at the end
+/// of the sniffed prefix
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && head.len() == SNIFF_LEN,
+    }
+}
+
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
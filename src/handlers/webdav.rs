@@ -0,0 +1,591 @@
+use crate::config::RouteConfig;
+use crate::http::{html_escape, http_date, percent_decode, percent_encode_path, percent_encode_segment, utc_components, HttpRequest, HttpResponse};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::put_file::ancestors_within_root;
+use super::{check_extension, check_quota, file_etag, is_internal_name, move_to_trash};
+
+/// Methods `webdav = on` adds to a route
+const DAV_METHODS: &[&str] = &["OPTIONS", "PROPFIND", "PROPPATCH", "MKCOL", "COPY", "MOVE"];
+
+const DAV_NS: &str = "DAV:";
+
+/// Live properties, in the order `allprop` lists them
+const LIVE_PROPS: &[&str] = &[
+    "creationdate",
+    "displayname",
+    "getcontentlength",
+    "getcontenttype",
+    "getetag",
+    "getlastmodified",
+    "lockdiscovery",
+    "resourcetype",
+    "supportedlock",
+];
+
+/// What the WebDAV handler needs from the router
+pub struct DavContext<'a> {
+    pub route: &'a RouteConfig,
+    /// Map a request path under the route to its file; None if it would
+    /// leave the root
+    pub map_path: &'a dyn Fn(&str) -> Option<String>,
+    /// Content-Type of a file, as GET would serve it
+    pub content_type: &'a dyn Fn(&str) -> String,
+}
+
+/// A namespace-qualified XML element name
+#[derive(Debug, Clone, PartialEq)]
+struct XmlName {
+    ns: String,
+    local: String,
+}
+
+impl XmlName {
+    fn is_dav(&self, local: &str) -> bool {
+        self.ns == DAV_NS && self.local == local
+    }
+}
+
+/// What a PROPFIND asks for
+enum PropRequest {
+    All,
+    Names,
+    Props(Vec<XmlName>),
+}
+
+/// The parts of a PROPFIND / PROPPATCH body we act on
+#[derive(Default)]
+struct PropBody {
+    allprop: bool,
+    propname: bool,
+    /// Children of every DAV:prop element
+    props: Vec<XmlName>,
+}
+
+pub fn is_webdav_method(method: &str) -> bool {
+    DAV_METHODS.iter().any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Handle a WebDAV class 1 request (RFC 4918) on a route with
+/// `webdav = on`. GET, PUT and DELETE stay with their usual handlers.
+/// Errors come back as a status code for the caller's error page.
+pub fn handle_webdav(request: &HttpRequest, context: &DavContext) -> Result<HttpResponse, u16> {
+    let file_path = (context.map_path)(&request.path).ok_or(403u16)?;
+    let path = Path::new(&file_path);
+
+    match request.method.to_ascii_uppercase().as_str() {
+        "OPTIONS" => Ok(options(context.route)),
+        "PROPFIND" => propfind(request, context, path),
+        "PROPPATCH" => proppatch(request, path),
+        "MKCOL" => mkcol(request, context.route, path),
+        "COPY" => copy_or_move(request, context, path, false),
+        "MOVE" => copy_or_move(request, context, path, true),
+        _ => Err(405),
+    }
+}
+
+fn options(route: &RouteConfig) -> HttpResponse {
+    let mut allow: Vec<String> = route.methods.clone();
+    for method in DAV_METHODS {
+        if !allow.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+            allow.push(method.to_string());
+        }
+    }
+
+    let mut response = HttpResponse::new(200, "OK");
+    response.set_header("DAV", "1");
+    response.set_header("Allow", &allow.join(", "));
+    // Lets Windows clients know they are talking to a DAV server
+    response.set_header("MS-Author-Via", "DAV");
+    response
+}
+
+/// PROPFIND with `Depth: 0` or `1`; infinite depth is refused (RFC 4918
+/// §9.1) since it would walk the whole tree in one request
+fn propfind(request: &HttpRequest, context: &DavContext, path: &Path) -> Result<HttpResponse, u16> {
+    let depth = match request.header("Depth").map(str::trim) {
+        Some("0") => 0,
+        Some("1") => 1,
+        Some(d) if !d.eq_ignore_ascii_case("infinity") => return Err(400),
+        _ => {
+            let mut response = xml_response(403, "Forbidden", "<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>\n");
+            response.set_header("Cache-Control", "no-store");
+            return Ok(response);
+        }
+    };
+
+    let body = parse_prop_body(&request.body).ok_or(400u16)?;
+    let wanted = if request.body.iter().all(u8::is_ascii_whitespace) || body.allprop {
+        PropRequest::All
+    } else if body.propname {
+        PropRequest::Names
+    } else {
+        PropRequest::Props(body.props)
+    };
+
+    let metadata = fs::metadata(path).map_err(|_| 404u16)?;
+
+    let decoded = percent_decode(&request.path);
+    let mut href = percent_encode_path(&decoded);
+    if metadata.is_dir() && !href.ends_with('/') {
+        href.push('/');
+    }
+    let display = decoded.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n");
+    xml.push_str(&prop_response(&href, display, path, &metadata, &wanted, context));
+
+    if depth == 1 && metadata.is_dir() {
+        let mut children: Vec<(String, Metadata)> = fs::read_dir(path)
+            .map_err(|_| 403u16)?
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Follow symlinks, as listings do; skip broken ones
+                let metadata = fs::metadata(entry.path()).ok()?;
+                (!is_internal_name(&name)).then_some((name, metadata))
+            })
+            .collect();
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, metadata) in children {
+            let mut child_href = format!("{}{}", href, percent_encode_segment(&name));
+            if metadata.is_dir() {
+                child_href.push('/');
+            }
+            xml.push_str(&prop_response(&child_href, &name, &path.join(&name), &metadata, &wanted, context));
+        }
+    }
+
+    xml.push_str("</D:multistatus>\n");
+    Ok(xml_response(207, "Multi-Status", &xml))
+}
+
+/// Only live properties exist here and none of them can be changed, so
+/// every property in the request fails with 403
+fn proppatch(request: &HttpRequest, path: &Path) -> Result<HttpResponse, u16> {
+    if fs::symlink_metadata(path).is_err() {
+        return Err(404);
+    }
+    let body = parse_prop_body(&request.body).ok_or(400u16)?;
+    if body.props.is_empty() {
+        return Err(400);
+    }
+
+    let props: String = body.props.iter().map(empty_element).collect();
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n<D:response><D:href>{}</D:href>{}</D:response>\n</D:multistatus>\n",
+        html_escape(&percent_encode_path(&percent_decode(&request.path))),
+        propstat(&props, "403 Forbidden")
+    );
+    Ok(xml_response(207, "Multi-Status", &xml))
+}
+
+/// MKCOL: create one directory; its parent must exist
+fn mkcol(request: &HttpRequest, route: &RouteConfig, path: &Path) -> Result<HttpResponse, u16> {
+    if request.body_len() > 0 {
+        return Err(415);
+    }
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(405);
+    }
+
+    let parent = path.parent().ok_or(403u16)?;
+    if !ancestors_within_root(parent, &route.root) {
+        return Err(403);
+    }
+    if !parent.is_dir() {
+        return Err(409);
+    }
+
+    fs::create_dir(path).map_err(|e| write_error(path, e))?;
+    Ok(HttpResponse::new(201, "Created"))
+}
+
+/// COPY and MOVE within the route: 201 if the destination is new, 204 if
+/// it replaced something (`Overwrite: F` makes that a 412 instead)
+fn copy_or_move(request: &HttpRequest, context: &DavContext, source: &Path, is_move: bool) -> Result<HttpResponse, u16> {
+    let route = context.route;
+    if source == Path::new(&route.root) {
+        return Err(403);
+    }
+    let metadata = fs::metadata(source).map_err(|_| 404u16)?;
+
+    let destination = destination(request, route)?;
+    if percent_decode(&destination).split('/').any(is_internal_name) {
+        return Err(403);
+    }
+    let dest_path = (context.map_path)(&destination).ok_or(403u16)?;
+    let dest = Path::new(&dest_path);
+    if dest == Path::new(&route.root) || dest == source {
+        return Err(403);
+    }
+    // A collection can't be put inside itself
+    if metadata.is_dir() && dest.starts_with(source) {
+        return Err(403);
+    }
+
+    let parent = dest.parent().ok_or(403u16)?;
+    if !ancestors_within_root(parent, &route.root) {
+        return Err(403);
+    }
+    if !parent.is_dir() {
+        return Err(409);
+    }
+
+    let overwrite = match request.header("Overwrite").map(str::trim) {
+        None | Some("T") | Some("t") => true,
+        Some("F") | Some("f") => false,
+        Some(_) => return Err(400),
+    };
+    // Collections are moved whole, and copied whole unless `Depth: 0`
+    let deep = match request.header("Depth").map(str::trim) {
+        None => true,
+        Some(d) if d.eq_ignore_ascii_case("infinity") => true,
+        Some("0") if !is_move => false,
+        Some(_) => return Err(400),
+    };
+
+    // The same restrictions as uploading the result
+    if !metadata.is_dir() {
+        check_extension(route, &dest.file_name().unwrap_or_default().to_string_lossy())?;
+    }
+    if !is_move {
+        check_quota(route, tree_size(source), None)?;
+    }
+
+    let existed = fs::symlink_metadata(dest).is_ok();
+    if existed {
+        if !overwrite {
+            return Err(412);
+        }
+        discard(route, dest).map_err(|e| write_error(dest, e))?;
+    }
+
+    let result = if is_move {
+        fs::rename(source, dest)
+    } else {
+        copy_tree(source, dest, deep)
+    };
+    result.map_err(|e| write_error(dest, e))?;
+
+    Ok(if existed {
+        HttpResponse::new(204, "No Content")
+    } else {
+        HttpResponse::new(201, "Created")
+    })
+}
+
+/// The path of the Destination header. It must name this server and the
+/// same route; anywhere else is someone else's resource (502).
+fn destination(request: &HttpRequest, route: &RouteConfig) -> Result<String, u16> {
+    let value = request.header("Destination").ok_or(400u16)?.trim();
+
+    let path = match value.split_once("://") {
+        Some((_, rest)) => {
+            let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            // Without a Host there is nothing to check the authority against
+            let host = request.header("Host").ok_or(400u16)?;
+            if !authority.eq_ignore_ascii_case(host.trim()) {
+                return Err(502);
+            }
+            path
+        }
+        None => value,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or("");
+
+    if !path.starts_with('/') {
+        return Err(400);
+    }
+    // Inside the route: `/dav` itself or something under `/dav/`, not `/davx`
+    let inside = path.strip_prefix(route.path.as_str()).is_some_and(|rest| {
+        rest.is_empty() || rest.starts_with('/') || route.path.ends_with('/')
+    });
+    if !inside {
+        return Err(502);
+    }
+    Ok(path.to_string())
+}
+
+/// Make way for an overwriting COPY / MOVE the same way DELETE would
+fn discard(route: &RouteConfig, path: &Path) -> io::Result<()> {
+    if route.trash {
+        move_to_trash(route, path)
+    } else if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Copy a file, or a directory and (if `deep`) everything in it. Symlinks
+/// inside are skipped rather than followed out of the root.
+fn copy_tree(source: &Path, dest: &Path, deep: bool) -> io::Result<()> {
+    if !fs::metadata(source)?.is_dir() {
+        return fs::copy(source, dest).map(|_| ());
+    }
+
+    fs::create_dir(dest)?;
+    if deep {
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_symlink() || is_internal_name(&name.to_string_lossy()) {
+                continue;
+            }
+            copy_tree(&entry.path(), &dest.join(&name), true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Bytes a copy of `path` would add
+fn tree_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|entries| entries.flatten().map(|e| tree_size(&e.path())).sum())
+            .unwrap_or(0),
+        Ok(meta) if meta.is_file() => meta.len(),
+        // The top level may be a symlink, which copy_tree follows
+        _ => fs::metadata(path).map(|m| if m.is_file() { m.len() } else { 0 }).unwrap_or(0),
+    }
+}
+
+fn write_error(path: &Path, e: io::Error) -> u16 {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => 403,
+        io::ErrorKind::AlreadyExists => 405,
+        _ => {
+            eprintln!("[webdav] failed to write {}: {}", path.display(), e);
+            500
+        }
+    }
+}
+
+/// One `<D:response>` of a PROPFIND multistatus
+fn prop_response(href: &str, display: &str, path: &Path, metadata: &Metadata, wanted: &PropRequest, context: &DavContext) -> String {
+    let mut found = String::new();
+    let mut missing = String::new();
+
+    match wanted {
+        PropRequest::All => {
+            for name in LIVE_PROPS {
+                if let Some(value) = live_prop(name, display, path, metadata, context) {
+                    found.push_str(&dav_element(name, &value));
+                }
+            }
+        }
+        PropRequest::Names => {
+            for name in LIVE_PROPS {
+                if live_prop(name, display, path, metadata, context).is_some() {
+                    found.push_str(&format!("<D:{}/>", name));
+                }
+            }
+        }
+        PropRequest::Props(props) => {
+            for prop in props {
+                let value = (prop.ns == DAV_NS)
+                    .then(|| live_prop(&prop.local, display, path, metadata, context))
+                    .flatten();
+                match value {
+                    Some(value) => found.push_str(&dav_element(&prop.local, &value)),
+                    None => missing.push_str(&empty_element(prop)),
+                }
+            }
+        }
+    }
+
+    let mut xml = format!("<D:response><D:href>{}</D:href>", html_escape(href));
+    if !found.is_empty() {
+        xml.push_str(&propstat(&found, "200 OK"));
+    }
+    if !missing.is_empty() {
+        xml.push_str(&propstat(&missing, "404 Not Found"));
+    }
+    xml.push_str("</D:response>\n");
+    xml
+}
+
+/// Value of a live property as XML content, or None if the resource
+/// doesn't have it
+fn live_prop(name: &str, display: &str, path: &Path, metadata: &Metadata, context: &DavContext) -> Option<String> {
+    let is_file = !metadata.is_dir();
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+    match name {
+        "creationdate" => Some(iso8601(metadata.created().unwrap_or(modified))),
+        "displayname" => Some(html_escape(display)),
+        "getcontentlength" if is_file => Some(metadata.len().to_string()),
+        "getcontenttype" if is_file => Some(html_escape(&(context.content_type)(&path.to_string_lossy()))),
+        "getetag" if is_file => Some(html_escape(&file_etag(metadata))),
+        "getlastmodified" => Some(http_date(modified)),
+        // No locking (class 1): present, but empty
+        "lockdiscovery" | "supportedlock" => Some(String::new()),
+        "resourcetype" => Some(if is_file { String::new() } else { "<D:collection/>".to_string() }),
+        _ => None,
+    }
+}
+
+fn propstat(props: &str, status: &str) -> String {
+    format!("<D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 {}</D:status></D:propstat>", props, status)
+}
+
+fn dav_element(name: &str, value: &str) -> String {
+    if value.is_empty() {
+        format!("<D:{}/>", name)
+    } else {
+        format!("<D:{}>{}</D:{}>", name, value, name)
+    }
+}
+
+/// An element in its own namespace, e.g. for a property we don't have
+fn empty_element(name: &XmlName) -> String {
+    if name.ns == DAV_NS {
+        format!("<D:{}/>", name.local)
+    } else {
+        format!("<{} xmlns=\"{}\"/>", name.local, html_escape(&name.ns))
+    }
+}
+
+fn xml_response(code: u16, message: &str, xml: &str) -> HttpResponse {
+    let mut response = HttpResponse::new(code, message);
+    response.set_header("Content-Type", "application/xml; charset=utf-8");
+    response.set_body(xml);
+    response
+}
+
+/// `2006-01-02T15:04:05Z`, as `creationdate` wants (RFC 3339)
+fn iso8601(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc_components(time);
+    format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+}
+
+/// Pick out `allprop`, `propname` and the children of `prop` elements from
+/// a PROPFIND or PROPPATCH body, resolving namespace prefixes. An empty
+/// body has none of them. None if the XML is malformed.
+fn parse_prop_body(body: &[u8]) -> Option<PropBody> {
+    let mut rest = std::str::from_utf8(body).ok()?;
+    let mut result = PropBody::default();
+    // Namespace declarations made by each open element, innermost last
+    let mut scopes: Vec<Vec<(String, String)>> = Vec::new();
+    let mut open: Vec<XmlName> = Vec::new();
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        // Declarations, comments and CDATA carry nothing we need
+        if let Some(after) = rest.strip_prefix('?') {
+            rest = &after[after.find("?>")? + 2..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("!--") {
+            rest = &after[after.find("-->")? + 3..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("![CDATA[") {
+            rest = &after[after.find("]]>")? + 3..];
+            continue;
+        }
+        // DOCTYPE (and with it entity definitions) is not accepted
+        if rest.starts_with('!') {
+            return None;
+        }
+
+        let end = tag_end(rest)?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = resolve_name(name.trim(), &scopes)?;
+            if open.pop()? != name {
+                return None;
+            }
+            scopes.pop();
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (raw_name, attrs) = tag.split_at(tag.find(|c: char| c.is_ascii_whitespace()).unwrap_or(tag.len()));
+
+        scopes.push(namespace_declarations(attrs)?);
+        let name = resolve_name(raw_name, &scopes)?;
+
+        if open.last().is_some_and(|parent| parent.is_dav("prop")) {
+            result.props.push(name.clone());
+        }
+        result.allprop |= name.is_dav("allprop");
+        result.propname |= name.is_dav("propname");
+
+        if self_closing {
+            scopes.pop();
+        } else {
+            open.push(name);
+        }
+    }
+
+    open.is_empty().then_some(result)
+}
+
+/// Index of the `>` closing a tag, skipping any inside quoted attributes
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `xmlns="..."` and `xmlns:p="..."` attributes as (prefix, namespace)
+fn namespace_declarations(attrs: &str) -> Option<Vec<(String, String)>> {
+    let mut declarations = Vec::new();
+    let mut rest = attrs.trim_start();
+
+    while !rest.is_empty() {
+        let eq = rest.find('=')?;
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let close = value[1..].find(quote)? + 1;
+
+        if name == "xmlns" {
+            declarations.push((String::new(), value[1..close].to_string()));
+        } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+            declarations.push((prefix.to_string(), value[1..close].to_string()));
+        }
+        rest = value[close + 1..].trim_start();
+    }
+    Some(declarations)
+}
+
+/// Resolve `prefix:local` against the declarations in scope. An unknown
+/// prefix is malformed; no prefix and no default namespace is the empty one.
+fn resolve_name(raw: &str, scopes: &[Vec<(String, String)>]) -> Option<XmlName> {
+    let (prefix, local) = raw.split_once(':').unwrap_or(("", raw));
+    if local.is_empty() {
+        return None;
+    }
+
+    let declared = scopes
+        .iter()
+        .rev()
+        .flat_map(|scope| scope.iter().rev())
+        .find(|(p, _)| p == prefix)
+        .map(|(_, ns)| ns.clone());
+
+    let ns = match declared {
+        Some(ns) => ns,
+        None if prefix.is_empty() => String::new(),
+        None => return None,
+    };
+    Some(XmlName { ns, local: local.to_string() })
+}
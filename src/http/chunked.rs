@@ -0,0 +1,194 @@
+/// Incremental decoder for `Transfer-Encoding: chunked` bodies, for when
+/// the body is consumed as it arrives instead of from a complete buffer
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+    /// Partial size/trailer line carried over between feeds
+    line: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkState {
+    /// Reading a `<hex size>[;ext]\r\n` line
+    Size,
+    /// Inside chunk data, with this many bytes left
+    Data(usize),
+    /// Expecting the CRLF that ends chunk data
+    DataEnd,
+    /// Reading trailer lines until an empty one
+    Trailer,
+    Done,
+}
+
+/// Malformed chunked framing
+#[derive(Debug)]
+pub struct ChunkedError;
+
+/// Longest size or trailer line we accept
+const MAX_LINE: usize = 8 * 1024;
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self { state: ChunkState::Size, line: Vec::new() }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == ChunkState::Done
+    }
+
+    /// Decode as much of `input` as possible, appending body bytes to `out`.
+    /// Returns how many input bytes were consumed; anything after the final
+    /// chunk (a pipelined request) is left unconsumed.
+    pub fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize, ChunkedError> {
+        let mut pos = 0;
+
+        while pos < input.len() && self.state != ChunkState::Done {
+            match self.state {
+                ChunkState::Size | ChunkState::Trailer => {
+                    let rest = &input[pos..];
+                    let (part, complete) = match rest.iter().position(|&b| b == b'\n') {
+                        Some(nl) => (&rest[..nl], true),
+                        None => (rest, false),
+                    };
+                    if self.line.len() + part.len() > MAX_LINE {
+                        return Err(ChunkedError);
+                    }
+                    self.line.extend_from_slice(part);
+                    pos += part.len();
+                    if complete {
+                        pos += 1;
+                        let line = std::mem::take(&mut self.line);
+                        self.end_line(&line)?;
+                    }
+                }
+                ChunkState::Data(remaining) => {
+                    let take = remaining.min(input.len() - pos);
+                    out.extend_from_slice(&input[pos..pos + take]);
+                    pos += take;
+                    self.state = if take == remaining {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(remaining - take)
+                    };
+                }
+                ChunkState::DataEnd => {
+                    match input[pos] {
+                        b'\r' => pos += 1,
+                        b'\n' => {
+                            pos += 1;
+                            self.state = ChunkState::Size;
+                        }
+                        _ => return Err(ChunkedError),
+                    }
+                }
+                ChunkState::Done => {}
+            }
+        }
+
+        Ok(pos)
+    }
+
+    fn end_line(&mut self, line: &[u8]) -> Result<(), ChunkedError> {
+        let line = std::str::from_utf8(line).map_err(|_| ChunkedError)?.trim_end_matches('\r');
+
+        match self.state {
+            ChunkState::Size => {
+                // Ignore chunk extensions
+                let size_str = line.split(';').next().unwrap_or("").trim();
+                // Hex digits only: from_str_radix would also take a sign
+                if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(ChunkedError);
+                }
+                let size = usize::from_str_radix(size_str, 16).map_err(|_| ChunkedError)?;
+                self.state = if size == 0 { ChunkState::Trailer } else { ChunkState::Data(size) };
+            }
+            ChunkState::Trailer if line.is_empty() => self.state = ChunkState::Done,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(input: &[u8]) -> Result<(Vec<u8>, usize, bool), ChunkedError> {
+        let mut decoder = ChunkedDecoder::new();
+        let mut out = Vec::new();
+        let used = decoder.feed(input, &mut out)?;
+        Ok((out, used, decoder.is_done()))
+    }
+
+    #[test]
+    fn chunks_with_extensions_and_trailers() {
+        let input = b"5;name=value\r\nhello\r\n6 ; a=\"b;c\"\r\n world\r\n0\r\nExpires: never\r\nX-Sum: 1\r\n\r\n";
+        let (out, used, done) = decode(input).unwrap();
+        assert_eq!(out, b"hello world");
+        assert_eq!(used, input.len());
+        assert!(done);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_body() {
+        let input = b"3\r\nabc\r\n0\r\n\r\nGET / HTTP/1.1\r\n";
+        let (out, used, done) = decode(input).unwrap();
+        assert_eq!(out, b"abc");
+        assert_eq!(&input[used..], b"GET / HTTP/1.1\r\n");
+        assert!(done);
+    }
+
+    #[test]
+    fn fed_one_byte_at_a_time() {
+        let input = b"a;x=1\r\n0123456789\r\n2\r\n!!\r\n0\r\nX-Trailer: y\r\n\r\n";
+        let mut decoder = ChunkedDecoder::new();
+        let mut out = Vec::new();
+        for byte in input.chunks(1) {
+            assert_eq!(decoder.feed(byte, &mut out).unwrap(), 1);
+        }
+        assert_eq!(out, b"0123456789!!");
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn truncated_body_is_not_done() {
+        for input in [&b"5\r\nhel"[..], b"5\r\nhello\r\n", b"5\r\nhello\r\n0\r\n", b"5\r\nhello\r\n0\r\nX-A: 1\r\n"] {
+            let (_, used, done) = decode(input).unwrap();
+            assert_eq!(used, input.len());
+            assert!(!done, "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn oversized_lines() {
+        let long_size = format!("{}1\r\nx\r\n0\r\n\r\n", "0".repeat(MAX_LINE + 1));
+        assert!(decode(long_size.as_bytes()).is_err());
+
+        // Also when the line arrives over several feeds
+        let mut decoder = ChunkedDecoder::new();
+        let mut out = Vec::new();
+        let piece = vec![b'0'; 1024];
+        let failed = (0..=MAX_LINE / 1024).any(|_| decoder.feed(&piece, &mut out).is_err());
+        assert!(failed);
+
+        let long_trailer = format!("0\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(decode(long_trailer.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn malformed_framing() {
+        assert!(decode(b"+5\r\nhello\r\n0\r\n\r\n").is_err());
+        assert!(decode(b"-1\r\n").is_err());
+        assert!(decode(b"\r\n").is_err());
+        assert!(decode(b"zz\r\n").is_err());
+        assert!(decode(b"ffffffffffffffffffff\r\n").is_err());
+        // Chunk data longer than its size
+        assert!(decode(b"3\r\nhello\r\n0\r\n\r\n").is_err());
+    }
+}
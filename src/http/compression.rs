@@ -0,0 +1,170 @@
+use super::deflate;
+use super::{HttpRequest, HttpResponse};
+
+/// Only these content types get compressed; everything else (images,
+/// video, archives) is usually compressed already
+const COMPRESSIBLE_PREFIXES: &[&str] = &["text/"];
+const COMPRESSIBLE_TYPES: &[&str] = &[
+    "application/javascript",
+    "application/json",
+    "application/ld+json",
+    "application/manifest+json",
+    "application/xml",
+    "application/xhtml+xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/wasm",
+    "application/x-javascript",
+    "image/svg+xml",
+    "image/x-icon",
+    "image/bmp",
+    "font/ttf",
+    "font/otf",
+    "application/vnd.ms-fontobject",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+/// Compress the response body in place if the client accepts gzip or
+/// deflate, the content type is compressible and the body is at least
+/// `min_length` bytes. Sets Content-Encoding and Vary accordingly.
+pub fn compress_response(request: &HttpRequest, response: &mut HttpResponse, min_length: usize) {
+    if !is_candidate(response, min_length) {
+        return;
+    }
+
+    // The representation depends on Accept-Encoding from here on,
+    // whether or not this particular client gets a compressed body
+    add_vary(response, "Accept-Encoding");
+
+    let encoding = match negotiate(request.header("Accept-Encoding").unwrap_or("")) {
+        Some(e) => e,
+        None => return,
+    };
+
+    let compressed = match encoding {
+        Encoding::Gzip => deflate::gzip(&response.body),
+        Encoding::Deflate => deflate::zlib(&response.body),
+    };
+
+    if compressed.len() >= response.body.len() {
+        return;
+    }
+
+    response.set_header("Content-Encoding", coding_name(encoding));
+    // Any length set by a handler (e.g. CGI) described the identity body
+    response.remove_header("Content-Length");
+    // Keep validators distinct from the identity representation's
+    if let Some(etag) = response.header("ETag").map(|e| e.to_string()) {
+        let tagged = match etag.strip_suffix('"') {
+            Some(open) => format!("{}-{}\"", open, coding_name(encoding)),
+            None => etag,
+        };
+        response.remove_header("ETag");
+        response.set_header("ETag", &tagged);
+    }
+    response.set_body_bytes(compressed);
+}
+
+fn coding_name(encoding: Encoding) -> &'static str {
+    match encoding {
+        Encoding::Gzip => "gzip",
+        Encoding::Deflate => "deflate",
+    }
+}
+
+fn is_candidate(response: &HttpResponse, min_length: usize) -> bool {
+    if response.stream.is_some() || response.body.len() < min_length {
+        return false;
+    }
+
+    // Never re-encode, and leave partial content alone: ranges refer
+    // to offsets in the identity representation
+    if response.status_code == 206
+        || response.header("Content-Encoding").is_some()
+        || response.header("Content-Range").is_some()
+    {
+        return false;
+    }
+
+    let content_type = response.header("Content-Type").unwrap_or("");
+    is_compressible_type(content_type)
+}
+
+fn is_compressible_type(content_type: &str) -> bool {
+    let base = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    COMPRESSIBLE_PREFIXES.iter().any(|p| base.starts_with(p))
+        || COMPRESSIBLE_TYPES.contains(&base.as_str())
+}
+
+/// Pick gzip or deflate from an Accept-Encoding header, honoring q-values.
+/// gzip wins ties.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut gzip_q = None;
+    let mut deflate_q = None;
+    let mut wildcard_q = None;
+
+    for item in accept_encoding.split(',') {
+        let (coding, q) = parse_coding(item);
+
+        match coding.as_str() {
+            "gzip" | "x-gzip" => gzip_q = Some(q),
+            "deflate" => deflate_q = Some(q),
+            "*" => wildcard_q = Some(q),
+            _ => {}
+        }
+    }
+
+    let gzip_q = gzip_q.or(wildcard_q).unwrap_or(0.0);
+    let deflate_q = deflate_q.or(wildcard_q).unwrap_or(0.0);
+
+    if gzip_q > 0.0 && gzip_q >= deflate_q {
+        Some(Encoding::Gzip)
+    } else if deflate_q > 0.0 {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Whether an Accept-Encoding header allows `coding` (explicitly or via `*`)
+pub fn accepts_encoding(accept_encoding: &str, coding: &str) -> bool {
+    let mut explicit = None;
+    let mut wildcard = None;
+    
+    for item in accept_encoding.split(',') {
+        let (name, q) = parse_coding(item);
+        if name.eq_ignore_ascii_case(coding) {
+            explicit = Some(q);
+        } else if name == "*" {
+            wildcard = Some(q);
+        }
+    }
+    
+    explicit.or(wildcard).is_some_and(|q| q > 0.0)
+}
+
+/// Split an Accept-Encoding item into its coding and q-value
+fn parse_coding(item: &str) -> (String, f32) {
+    let mut parts = item.split(';');
+    let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+    let q = parts
+        .find_map(|p| p.trim().strip_prefix("q=").and_then(|v| v.trim().parse::<f32>().ok()))
+        .unwrap_or(1.0);
+    (coding, q)
+}
+
+/// Add a field name to the Vary header without duplicating it
+pub fn add_vary(response: &mut HttpResponse, field: &str) {
+    let merged = match response.header("Vary") {
+        Some(existing) if existing.split(',').any(|f| f.trim().eq_ignore_ascii_case(field)) => return,
+        Some(existing) if !existing.trim().is_empty() => format!("{}, {}", existing, field),
+        _ => field.to_string(),
+    };
+    response.remove_header("Vary");
+    response.set_header("Vary", &merged);
+}
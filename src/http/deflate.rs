@@ -0,0 +1,774 @@
+//! Minimal DEFLATE (RFC 1951) encoder with gzip (RFC 1952) and zlib
+//! (RFC 1950) framing, plus the CRC-32 / Adler-32 checksums they need.
+//!
+//! Compression uses hash-chained LZ77 matching and a dynamic Huffman code
+//! per block, falling back to stored blocks when that would be larger.
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const WINDOW_MASK: usize = WINDOW_SIZE - 1;
+const HASH_BITS: usize = 15;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many previous positions to try per match search
+const MAX_CHAIN: usize = 128;
+/// Stop searching once a match this long is found
+const NICE_MATCH: usize = 128;
+/// LZ77 symbols per emitted block
+const BLOCK_SYMBOLS: usize = 16 * 1024;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are transmitted
+const CL_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Compress `data` into a gzip member
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+    out.extend_from_slice(&deflate_raw(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Compress `data` into a zlib stream (what HTTP calls `deflate`)
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate_raw(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Compress `data` into a raw DEFLATE stream
+pub fn deflate_raw(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let symbols = lz77(data);
+
+    if symbols.is_empty() {
+        // Empty input: a single final block with just end-of-block
+        writer.write_bits(1, 1);
+        writer.write_bits(1, 2); // fixed Huffman
+        writer.write_bits(0, 7); // code 256
+        return writer.finish();
+    }
+
+    let mut offset = 0; // byte offset into `data` where the block starts
+    let chunks: Vec<&[Symbol]> = symbols.chunks(BLOCK_SYMBOLS).collect();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let is_final = i + 1 == chunks.len();
+        let block_len: usize = chunk.iter().map(|s| s.input_len()).sum();
+        write_block(&mut writer, chunk, &data[offset..offset + block_len], is_final);
+        offset += block_len;
+    }
+
+    writer.finish()
+}
+
+/// CRC-32 (IEEE 802.3), as used by gzip and zip
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continue a CRC-32 computation over another chunk of data
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+#[derive(Clone, Copy)]
+enum Symbol {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+impl Symbol {
+    fn input_len(&self) -> usize {
+        match self {
+            Symbol::Literal(_) => 1,
+            Symbol::Match { length, .. } => *length as usize,
+        }
+    }
+}
+
+fn hash3(data: &[u8], pos: usize) -> usize {
+    let v = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Greedy LZ77 with hash chains over a 32 KiB window
+fn lz77(data: &[u8]) -> Vec<Symbol> {
+    let mut symbols = Vec::with_capacity(data.len() / 2);
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, pos: usize| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash3(data, pos);
+            prev[pos & WINDOW_MASK] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash3(data, pos)];
+            let mut chain = 0;
+
+            while candidate != usize::MAX && chain < MAX_CHAIN {
+                let dist = pos - candidate;
+                if dist > WINDOW_SIZE || dist == 0 {
+                    break;
+                }
+
+                if data[candidate + best_len.min(max_len - 1)] == data[pos + best_len.min(max_len - 1)] {
+                    let len = data[candidate..]
+                        .iter()
+                        .zip(&data[pos..pos + max_len])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if len > best_len {
+                        best_len = len;
+                        best_dist = dist;
+                        if len >= NICE_MATCH.min(max_len) {
+                            break;
+                        }
+                    }
+                }
+
+                let next = prev[candidate & WINDOW_MASK];
+                // Chain entries are only valid while they point backwards
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            symbols.push(Symbol::Match { length: best_len as u16, distance: best_dist as u16 });
+            for p in pos..pos + best_len {
+                insert(&mut head, &mut prev, p);
+            }
+            pos += best_len;
+        } else {
+            symbols.push(Symbol::Literal(data[pos]));
+            insert(&mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+
+    symbols
+}
+
+fn length_code(length: u16) -> (usize, u8, u16) {
+    let idx = LENGTH_BASE.iter().rposition(|&base| base <= length).unwrap_or(0);
+    (257 + idx, LENGTH_EXTRA[idx], length - LENGTH_BASE[idx])
+}
+
+fn dist_code(distance: u16) -> (usize, u8, u16) {
+    let idx = DIST_BASE.iter().rposition(|&base| base <= distance).unwrap_or(0);
+    (idx, DIST_EXTRA[idx], distance - DIST_BASE[idx])
+}
+
+fn write_block(writer: &mut BitWriter, symbols: &[Symbol], raw: &[u8], is_final: bool) {
+    let mut lit_freq = [0u32; 286];
+    let mut dist_freq = [0u32; 30];
+    for sym in symbols {
+        match *sym {
+            Symbol::Literal(b) => lit_freq[b as usize] += 1,
+            Symbol::Match { length, distance } => {
+                lit_freq[length_code(length).0] += 1;
+                dist_freq[dist_code(distance).0] += 1;
+            }
+        }
+    }
+    lit_freq[256] = 1;
+
+    let lit_lengths = huffman_lengths(&lit_freq, 15);
+    let mut dist_lengths = huffman_lengths(&dist_freq, 15);
+    // At least one distance code must be present, even if unused
+    if dist_lengths.iter().all(|&l| l == 0) {
+        dist_lengths[0] = 1;
+    }
+
+    let hlit = 257.max(lit_lengths.iter().rposition(|&l| l != 0).unwrap_or(0) + 1);
+    let hdist = 1.max(dist_lengths.iter().rposition(|&l| l != 0).unwrap_or(0) + 1);
+
+    let mut all_lengths = lit_lengths[..hlit].to_vec();
+    all_lengths.extend_from_slice(&dist_lengths[..hdist]);
+    let cl_symbols = run_length_encode(&all_lengths);
+
+    let mut cl_freq = [0u32; 19];
+    for &(sym, _) in &cl_symbols {
+        cl_freq[sym as usize] += 1;
+    }
+    // Decoders reject an incomplete code length code, so use at least two symbols
+    if cl_freq.iter().filter(|&&f| f > 0).count() < 2 {
+        let unused = if cl_freq[0] == 0 { 0 } else { 1 };
+        cl_freq[unused] = 1;
+    }
+    let cl_lengths = huffman_lengths(&cl_freq, 7);
+    let hclen = 4.max(CL_ORDER.iter().rposition(|&i| cl_lengths[i] != 0).unwrap_or(0) + 1);
+
+    // Estimate the dynamic block size in bits to compare against stored
+    let mut dynamic_bits = 3 + 5 + 5 + 4 + 3 * hclen as u64;
+    for &(sym, _) in &cl_symbols {
+        dynamic_bits += cl_lengths[sym as usize] as u64
+            + match sym { 16 => 2, 17 => 3, 18 => 7, _ => 0 };
+    }
+    for sym in symbols {
+        dynamic_bits += match *sym {
+            Symbol::Literal(b) => lit_lengths[b as usize] as u64,
+            Symbol::Match { length, distance } => {
+                let (lc, lextra, _) = length_code(length);
+                let (dc, dextra, _) = dist_code(distance);
+                (lit_lengths[lc] + lextra + dist_lengths[dc] + dextra) as u64
+            }
+        };
+    }
+    dynamic_bits += lit_lengths[256] as u64;
+
+    let stored_bits = (raw.len() as u64 + 4 * raw.len().div_ceil(65535).max(1) as u64) * 8 + 8;
+    if stored_bits < dynamic_bits {
+        write_stored(writer, raw, is_final);
+        return;
+    }
+
+    let lit_codes = canonical_codes(&lit_lengths);
+    let dist_codes = canonical_codes(&dist_lengths);
+    let cl_codes = canonical_codes(&cl_lengths);
+
+    writer.write_bits(is_final as u32, 1);
+    writer.write_bits(2, 2); // dynamic Huffman
+    writer.write_bits((hlit - 257) as u32, 5);
+    writer.write_bits((hdist - 1) as u32, 5);
+    writer.write_bits((hclen - 4) as u32, 4);
+    for &i in CL_ORDER.iter().take(hclen) {
+        writer.write_bits(cl_lengths[i] as u32, 3);
+    }
+    for &(sym, extra) in &cl_symbols {
+        let sym = sym as usize;
+        writer.write_code(cl_codes[sym], cl_lengths[sym]);
+        match sym {
+            16 => writer.write_bits(extra as u32, 2),
+            17 => writer.write_bits(extra as u32, 3),
+            18 => writer.write_bits(extra as u32, 7),
+            _ => {}
+        }
+    }
+
+    for sym in symbols {
+        match *sym {
+            Symbol::Literal(b) => writer.write_code(lit_codes[b as usize], lit_lengths[b as usize]),
+            Symbol::Match { length, distance } => {
+                let (lc, lextra_bits, lextra) = length_code(length);
+                writer.write_code(lit_codes[lc], lit_lengths[lc]);
+                writer.write_bits(lextra as u32, lextra_bits);
+                let (dc, dextra_bits, dextra) = dist_code(distance);
+                writer.write_code(dist_codes[dc], dist_lengths[dc]);
+                writer.write_bits(dextra as u32, dextra_bits);
+            }
+        }
+    }
+    writer.write_code(lit_codes[256], lit_lengths[256]);
+}
+
+fn write_stored(writer: &mut BitWriter, raw: &[u8], is_final: bool) {
+    let chunks: Vec<&[u8]> = if raw.is_empty() { vec![raw] } else { raw.chunks(65535).collect() };
+    for (i, chunk) in chunks.iter().enumerate() {
+        let last = is_final && i + 1 == chunks.len();
+        writer.write_bits(last as u32, 1);
+        writer.write_bits(0, 2);
+        writer.align_byte();
+        let len = chunk.len() as u16;
+        writer.write_bytes(&len.to_le_bytes());
+        writer.write_bytes(&(!len).to_le_bytes());
+        writer.write_bytes(chunk);
+    }
+}
+
+/// Encode code lengths with the 16/17/18 run-length symbols.
+/// Returns (symbol, extra bits value) pairs.
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+
+        if len == 0 && run >= 11 {
+            let n = run.min(138);
+            out.push((18, (n - 11) as u8));
+            i += n;
+        } else if len == 0 && run >= 3 {
+            out.push((17, (run - 3) as u8));
+            i += run;
+        } else if len != 0 && run >= 4 {
+            out.push((len, 0));
+            let n = (run - 1).min(6);
+            out.push((16, (n - 3) as u8));
+            i += 1 + n;
+        } else {
+            out.push((len, 0));
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Build length-limited Huffman code lengths. When the optimal tree is
+/// too deep, frequencies are flattened and the tree rebuilt.
+fn huffman_lengths(freq: &[u32], max_bits: u8) -> Vec<u8> {
+    let mut freq: Vec<u64> = freq.iter().map(|&f| f as u64).collect();
+    loop {
+        let lengths = build_huffman(&freq);
+        if lengths.iter().all(|&l| l <= max_bits) {
+            return lengths;
+        }
+        for f in freq.iter_mut().filter(|f| **f > 0) {
+            *f = (*f >> 1) + 1;
+        }
+    }
+}
+
+fn build_huffman(freq: &[u64]) -> Vec<u8> {
+    let mut lengths = vec![0u8; freq.len()];
+    let used: Vec<usize> = (0..freq.len()).filter(|&i| freq[i] > 0).collect();
+
+    match used.len() {
+        0 => return lengths,
+        1 => {
+            lengths[used[0]] = 1;
+            return lengths;
+        }
+        _ => {}
+    }
+
+    // Nodes: leaves first, then internal nodes; parent links give depths
+    let mut weight: Vec<u64> = used.iter().map(|&i| freq[i]).collect();
+    let mut parent: Vec<usize> = vec![usize::MAX; used.len()];
+    let mut heap: std::collections::BinaryHeap<std::cmp::Reverse<(u64, usize)>> =
+        (0..used.len()).map(|i| std::cmp::Reverse((weight[i], i))).collect();
+
+    while heap.len() > 1 {
+        let std::cmp::Reverse((w1, a)) = heap.pop().unwrap();
+        let std::cmp::Reverse((w2, b)) = heap.pop().unwrap();
+        let node = weight.len();
+        weight.push(w1 + w2);
+        parent.push(usize::MAX);
+        parent[a] = node;
+        parent[b] = node;
+        heap.push(std::cmp::Reverse((w1 + w2, node)));
+    }
+
+    for (leaf, &symbol) in used.iter().enumerate() {
+        let mut depth = 0u8;
+        let mut node = leaf;
+        while parent[node] != usize::MAX {
+            node = parent[node];
+            depth = depth.saturating_add(1);
+        }
+        lengths[symbol] = depth;
+    }
+    lengths
+}
+
+/// Assign canonical Huffman codes (RFC 1951 section 3.2.2)
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let max = *lengths.iter().max().unwrap_or(&0) as usize;
+    let mut bl_count = vec![0u16; max + 1];
+    for &l in lengths {
+        if l > 0 {
+            bl_count[l as usize] += 1;
+        }
+    }
+
+    let mut next_code = vec![0u16; max + 2];
+    let mut code = 0u16;
+    for bits in 1..=max {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+
+    lengths
+        .iter()
+        .map(|&l| {
+            if l == 0 {
+                0
+            } else {
+                let c = next_code[l as usize];
+                next_code[l as usize] += 1;
+                c
+            }
+        })
+        .collect()
+}
+
+/// LSB-first bit writer
+struct BitWriter {
+    out: Vec<u8>,
+    bit_buf: u64,
+    bit_count: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { out: Vec::new(), bit_buf: 0, bit_count: 0 }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u8) {
+        if count == 0 {
+            return;
+        }
+        self.bit_buf |= (value as u64 & ((1u64 << count) - 1)) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bit_buf as u8);
+            self.bit_buf >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u16, length: u8) {
+        let reversed = code.reverse_bits() >> (16 - length as u32);
+        self.write_bits(reversed as u32, length);
+    }
+
+    fn align_byte(&mut self) {
+        if self.bit_count > 0 {
+            self.out.push(self.bit_buf as u8);
+            self.bit_buf = 0;
+            self.bit_count = 0;
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align_byte();
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal inflater (RFC 1951) to check the encoder against
+    struct Inflater<'a> {
+        data: &'a [u8],
+        pos: usize,
+        bit: u32,
+    }
+
+    /// Canonical Huffman decoding table: codes per length, symbols in order
+    struct Huffman {
+        counts: [u16; 16],
+        symbols: Vec<u16>,
+    }
+
+    impl Huffman {
+        fn new(lengths: &[u8]) -> Self {
+            let mut counts = [0u16; 16];
+            for &l in lengths {
+                counts[l as usize] += 1;
+            }
+            counts[0] = 0;
+            let mut symbols = Vec::new();
+            for len in 1..16 {
+                for (sym, &l) in lengths.iter().enumerate() {
+                    if l as usize == len {
+                        symbols.push(sym as u16);
+                    }
+                }
+            }
+            Self { counts, symbols }
+        }
+    }
+
+    impl Inflater<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let byte = self.data[self.pos];
+                value |= ((byte >> self.bit) as u32 & 1) << i;
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.bit = 0;
+                    self.pos += 1;
+                }
+            }
+            value
+        }
+
+        fn decode(&mut self, table: &Huffman) -> u16 {
+            let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+            for len in 1..16 {
+                code |= self.bits(1) as i32;
+                let count = table.counts[len] as i32;
+                if code - first < count {
+                    return table.symbols[(index + code - first) as usize];
+                }
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            panic!("invalid Huffman code");
+        }
+
+        fn dynamic_tables(&mut self) -> (Huffman, Huffman) {
+            let hlit = self.bits(5) as usize + 257;
+            let hdist = self.bits(5) as usize + 1;
+            let hclen = self.bits(4) as usize + 4;
+            let mut cl_lengths = [0u8; 19];
+            for &i in CL_ORDER.iter().take(hclen) {
+                cl_lengths[i] = self.bits(3) as u8;
+            }
+            let cl = Huffman::new(&cl_lengths);
+
+            let mut lengths = Vec::new();
+            while lengths.len() < hlit + hdist {
+                match self.decode(&cl) {
+                    sym @ 0..=15 => lengths.push(sym as u8),
+                    16 => {
+                        let last = *lengths.last().expect("repeat with no previous length");
+                        let repeat = 3 + self.bits(2);
+                        lengths.extend(std::iter::repeat_n(last, repeat as usize));
+                    }
+                    17 => {
+                        let repeat = 3 + self.bits(3);
+                        lengths.extend(std::iter::repeat_n(0, repeat as usize));
+                    }
+                    _ => {
+                        let repeat = 11 + self.bits(7);
+                        lengths.extend(std::iter::repeat_n(0, repeat as usize));
+                    }
+                }
+            }
+            assert_eq!(lengths.len(), hlit + hdist, "code lengths overrun");
+            (Huffman::new(&lengths[..hlit]), Huffman::new(&lengths[hlit..]))
+        }
+
+        /// The decoded data and the block types seen, in order
+        fn inflate(&mut self) -> (Vec<u8>, Vec<u32>) {
+            let mut out = Vec::new();
+            let mut types = Vec::new();
+            loop {
+                let is_final = self.bits(1) == 1;
+                let block_type = self.bits(2);
+                types.push(block_type);
+                match block_type {
+                    0 => {
+                        if self.bit > 0 {
+                            self.bit = 0;
+                            self.pos += 1;
+                        }
+                        let len = u16::from_le_bytes([self.data[self.pos], self.data[self.pos + 1]]);
+                        let nlen = u16::from_le_bytes([self.data[self.pos + 2], self.data[self.pos + 3]]);
+                        assert_eq!(len, !nlen, "stored block length check");
+                        self.pos += 4;
+                        out.extend_from_slice(&self.data[self.pos..self.pos + len as usize]);
+                        self.pos += len as usize;
+                    }
+                    1 | 2 => {
+                        let (lit, dist) = if block_type == 1 {
+                            let mut lengths = [8u8; 288];
+                            lengths[144..256].fill(9);
+                            lengths[256..280].fill(7);
+                            (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                        } else {
+                            self.dynamic_tables()
+                        };
+                        loop {
+                            let sym = self.decode(&lit) as usize;
+                            if sym < 256 {
+                                out.push(sym as u8);
+                                continue;
+                            }
+                            if sym == 256 {
+                                break;
+                            }
+                            let idx = sym - 257;
+                            let length = LENGTH_BASE[idx] as usize + self.bits(LENGTH_EXTRA[idx] as u32) as usize;
+                            let dc = self.decode(&dist) as usize;
+                            let distance = DIST_BASE[dc] as usize + self.bits(DIST_EXTRA[dc] as u32) as usize;
+                            assert!(distance <= out.len() && distance <= WINDOW_SIZE, "distance out of range");
+                            for _ in 0..length {
+                                out.push(out[out.len() - distance]);
+                            }
+                        }
+                    }
+                    _ => panic!("reserved block type"),
+                }
+                if is_final {
+                    break;
+                }
+            }
+            (out, types)
+        }
+
+        /// Bytes consumed, counting a partly used last byte
+        fn consumed(&self) -> usize {
+            self.pos + (self.bit > 0) as usize
+        }
+    }
+
+    fn inflate(data: &[u8]) -> (Vec<u8>, Vec<u32>, usize) {
+        let mut inflater = Inflater { data, pos: 0, bit: 0 };
+        let (out, types) = inflater.inflate();
+        (out, types, inflater.consumed())
+    }
+
+    /// Deterministic incompressible bytes (xorshift)
+    fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn round_trip(data: &[u8]) -> Vec<u32> {
+        let compressed = deflate_raw(data);
+        let (out, types, consumed) = inflate(&compressed);
+        assert_eq!(out, data);
+        assert_eq!(consumed, compressed.len(), "trailing bytes after the final block");
+        types
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xcbf4_3926);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(deflate_raw(b""), [0x03, 0x00]);
+        assert_eq!(round_trip(b""), [1]);
+        assert_eq!(gzip(b""), [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3, 0x03, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(200);
+        let types = round_trip(&text);
+        assert!(types.iter().all(|&t| t == 2));
+        assert!(deflate_raw(&text).len() < text.len() / 10);
+
+        for len in [1, 2, 3, 4, 257, 258, 259] {
+            round_trip(&b"a".repeat(len));
+            round_trip(&noise(len, 7));
+        }
+    }
+
+    #[test]
+    fn incompressible_input_is_stored() {
+        let data = noise(200_000, 1);
+        let types = round_trip(&data);
+        assert!(types.iter().all(|&t| t == 0), "{types:?}");
+        // Overhead is a few bytes per block
+        assert!(deflate_raw(&data).len() < data.len() + 100);
+    }
+
+    #[test]
+    fn stored_blocks_split_at_65535() {
+        let data = noise(150_000, 3);
+        let mut writer = BitWriter::new();
+        write_stored(&mut writer, &data, true);
+        let (out, types, _) = inflate(&writer.finish());
+        assert_eq!(out, data);
+        assert_eq!(types, [0, 0, 0]);
+    }
+
+    #[test]
+    fn longest_match_at_window_end() {
+        // The same 32 KiB twice: the second copy is all 258-byte matches
+        // reaching back the full window
+        let block = noise(WINDOW_SIZE, 5);
+        let data = [&block[..], &block[..], &block[..1000]].concat();
+        let symbols = lz77(&data);
+        assert!(symbols
+            .iter()
+            .any(|s| matches!(s, Symbol::Match { length: 258, distance } if *distance as usize == WINDOW_SIZE)));
+        assert!(symbols.iter().all(|s| match s {
+            Symbol::Match { length, distance } => (MIN_MATCH..=MAX_MATCH).contains(&(*length as usize)) && *distance as usize <= WINDOW_SIZE,
+            Symbol::Literal(_) => true,
+        }));
+        round_trip(&data);
+    }
+
+    #[test]
+    fn gzip_trailer() {
+        let data = b"hello hello hello hello, gzip".repeat(50);
+        let member = gzip(&data);
+        assert_eq!(member[..4], [0x1f, 0x8b, 8, 0]);
+
+        let (out, _, consumed) = inflate(&member[10..]);
+        assert_eq!(out, data);
+        let trailer = &member[10 + consumed..];
+        assert_eq!(trailer.len(), 8);
+        assert_eq!(trailer[..4], crc32(&data).to_le_bytes());
+        assert_eq!(trailer[4..], (data.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn zlib_header_and_adler() {
+        let data = b"zlib wraps deflate with a header and an Adler-32".repeat(20);
+        let stream = zlib(&data);
+        assert_eq!((stream[0] as u16 * 256 + stream[1] as u16) % 31, 0);
+        let (out, _, consumed) = inflate(&stream[2..]);
+        assert_eq!(out, data);
+        assert_eq!(stream[2 + consumed..], adler32(&data).to_be_bytes());
+    }
+}
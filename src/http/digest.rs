@@ -0,0 +1,129 @@
+use crate::crypto::{base64_decode, base64_encode, md5, sha256};
+
+use super::{HttpRequest, HttpResponse};
+
+/// Request headers that carry a digest of the body
+const DIGEST_HEADERS: &[&str] = &["Content-MD5", "Digest", "Content-Digest", "Repr-Digest"];
+
+/// Digests of a request body in every algorithm we can check
+pub struct BodyDigest {
+    pub sha256: [u8; 32],
+    pub md5: [u8; 16],
+}
+
+impl BodyDigest {
+    /// Digest the body of `request`: taken from the spool for a body on
+    /// disk, computed here for one in memory
+    pub fn of(request: &HttpRequest) -> Self {
+        match &request.body_file {
+            Some(body_file) => Self { sha256: body_file.sha256, md5: body_file.md5 },
+            None => Self { sha256: sha256(&request.body), md5: md5(&request.body) },
+        }
+    }
+
+    /// Add `Repr-Digest` (RFC 9530) and `Digest` (RFC 3230) for this body,
+    /// so the client can confirm what was stored
+    pub fn set_headers(&self, response: &mut HttpResponse) {
+        let sha256 = base64_encode(&self.sha256);
+        let md5 = base64_encode(&self.md5);
+        response.set_header("Repr-Digest", &format!("sha-256=:{}:, md5=:{}:", sha256, md5));
+        response.set_header("Digest", &format!("sha-256={},md5={}", sha256, md5));
+    }
+}
+
+/// Whether the client sent any digest of the body
+pub fn has_body_digest(request: &HttpRequest) -> bool {
+    DIGEST_HEADERS.iter().any(|name| request.header(name).is_some())
+}
+
+/// Check `digest` against every `Content-MD5`, `Digest`, `Content-Digest`
+/// and `Repr-Digest` header of the request. Algorithms other than SHA-256
+/// and MD5 are skipped. On failure, returns the header that didn't match
+/// or couldn't be parsed.
+pub fn verify_body_digest(request: &HttpRequest, digest: &BodyDigest) -> Result<(), &'static str> {
+    if let Some(value) = request.header("Content-MD5")
+        && base64_decode(value.trim()).as_deref() != Some(&digest.md5[..])
+    {
+        return Err("Content-MD5");
+    }
+
+    // RFC 3230: `SHA-256=<base64>, MD5=<base64>`
+    if let Some(value) = request.header("Digest") {
+        for (algorithm, encoded) in value.split(',').filter_map(|item| item.trim().split_once('=')) {
+            if !matches_algorithm(digest, algorithm, encoded.trim()) {
+                return Err("Digest");
+            }
+        }
+    }
+
+    // RFC 9530 dictionaries: `sha-256=:<base64>:`. With no content coding
+    // the content and representation digests are the same bytes.
+    for name in ["Content-Digest", "Repr-Digest"] {
+        if let Some(value) = request.header(name) {
+            for (algorithm, encoded) in value.split(',').filter_map(|item| item.trim().split_once('=')) {
+                // Parameters after `;` don't change the value
+                let encoded = encoded.split(';').next().unwrap_or("").trim();
+                let encoded = match encoded.strip_prefix(':').and_then(|e| e.strip_suffix(':')) {
+                    Some(encoded) => encoded,
+                    None => return Err(name),
+                };
+                if !matches_algorithm(digest, algorithm, encoded) {
+                    return Err(name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `encoded` is the body's digest under `algorithm`; true for an
+/// algorithm we don't implement
+fn matches_algorithm(digest: &BodyDigest, algorithm: &str, encoded: &str) -> bool {
+    let expected: &[u8] = match algorithm.trim().to_ascii_lowercase().as_str() {
+        "sha-256" => &digest.sha256,
+        "md5" => &digest.md5,
+        _ => return true,
+    };
+    base64_decode(encoded).as_deref() == Some(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // MD5 and SHA-256 of "hello world"
+    const MD5: &str = "XrY7u+Ae7tCTyyK7j1rNww==";
+    const SHA256: &str = "uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=";
+
+    fn check(header: &str) -> Result<(), &'static str> {
+        let raw = format!("PUT /a HTTP/1.1\r\nHost: x\r\n{}\r\nContent-Length: 11\r\n\r\nhello world", header);
+        let request = HttpRequest::parse(raw.as_bytes()).unwrap();
+        verify_body_digest(&request, &BodyDigest::of(&request))
+    }
+
+    #[test]
+    fn matching_digests() {
+        assert_eq!(check(&format!("Content-MD5: {}", MD5)), Ok(()));
+        assert_eq!(check(&format!("Digest: SHA-256={}, MD5={}", SHA256, MD5)), Ok(()));
+        assert_eq!(check(&format!("Content-Digest: sha-256=:{}:", SHA256)), Ok(()));
+        assert_eq!(check(&format!("Repr-Digest: md5=:{}:;p=1, sha-256=:{}:", MD5, SHA256)), Ok(()));
+        // Algorithms we can't check are skipped
+        assert_eq!(check("Digest: SHA-512=AAAA"), Ok(()));
+    }
+
+    #[test]
+    fn mismatching_digests() {
+        assert_eq!(check(&format!("Content-MD5: {}", SHA256)), Err("Content-MD5"));
+        assert_eq!(check("Content-MD5: not base64!"), Err("Content-MD5"));
+        assert_eq!(check(&format!("Digest: SHA-256={}, MD5=AAAA", SHA256)), Err("Digest"));
+        assert_eq!(check(&format!("Content-Digest: sha-256={}", SHA256)), Err("Content-Digest"));
+        assert_eq!(check(&format!("Repr-Digest: md5=:{}:", SHA256)), Err("Repr-Digest"));
+    }
+
+    #[test]
+    fn no_digest_headers() {
+        let request = HttpRequest::parse(b"PUT /a HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\nhi").unwrap();
+        assert!(!has_body_digest(&request));
+        assert_eq!(verify_body_digest(&request, &BodyDigest::of(&request)), Ok(()));
+    }
+}
@@ -0,0 +1,352 @@
+/// Escape a string for use inside a JSON string literal (without quotes)
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Quote and escape a string as a JSON string literal
+pub fn json_string(s: &str) -> String {
+    format!("\"{}\"", json_escape(s))
+}
+
+/// Deepest nesting `parse_json` accepts
+const MAX_DEPTH: usize = 64;
+
+/// A parsed JSON value. Numbers keep their source text so large integers
+/// survive being passed on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// A member of an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Compact JSON text for the value
+    pub fn to_json(&self) -> String {
+        match self {
+            JsonValue::Null => "null".to_string(),
+            JsonValue::Bool(b) => b.to_string(),
+            JsonValue::Number(n) => n.clone(),
+            JsonValue::String(s) => json_string(s),
+            JsonValue::Array(items) => {
+                format!("[{}]", items.iter().map(JsonValue::to_json).collect::<Vec<_>>().join(","))
+            }
+            JsonValue::Object(members) => format!(
+                "{{{}}}",
+                members
+                    .iter()
+                    .map(|(k, v)| format!("{}:{}", json_string(k), v.to_json()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// Parse a complete JSON document (RFC 8259); None if it isn't one
+pub fn parse_json(text: &str) -> Option<JsonValue> {
+    let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    (parser.pos == parser.bytes.len()).then_some(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Option<JsonValue> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match *self.bytes.get(self.pos)? {
+            b'{' => self.object(depth),
+            b'[' => self.array(depth),
+            b'"' => self.string().map(JsonValue::String),
+            b't' => self.literal("true", JsonValue::Bool(true)),
+            b'f' => self.literal("false", JsonValue::Bool(false)),
+            b'n' => self.literal("null", JsonValue::Null),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => None,
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Option<JsonValue> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Some(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return None;
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Some(JsonValue::Object(members));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Option<JsonValue> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Some(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Some(JsonValue::Array(items));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let byte = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+            match byte {
+                b'"' => return String::from_utf8(out).ok(),
+                b'\\' => {
+                    let escaped = *self.bytes.get(self.pos)?;
+                    self.pos += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return None,
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                0x00..=0x1f => return None,
+                _ => out.push(byte),
+            }
+        }
+    }
+
+    /// The character after `\u`, joining a UTF-16 surrogate pair
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.bytes.get(self.pos..self.pos + 2) != Some(b"\\u") {
+            return None;
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.bytes.get(self.pos..self.pos + 4)?;
+        // from_str_radix alone would also take a sign
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        self.pos += 4;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+
+    /// `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`
+    fn number(&mut self) -> Option<JsonValue> {
+        let start = self.pos;
+        self.eat(b'-');
+        if !self.eat(b'0') && self.digits() == 0 {
+            return None;
+        }
+        if self.eat(b'.') && self.digits() == 0 {
+            return None;
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            if !self.eat(b'+') {
+                self.eat(b'-');
+            }
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.pos]).ok()?;
+        text.parse::<f64>().ok().filter(|n| n.is_finite())?;
+        Some(JsonValue::Number(text.to_string()))
+    }
+
+    /// Skip a run of digits, returning how many there were
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn literal(&mut self, word: &str, value: JsonValue) -> Option<JsonValue> {
+        if self.bytes.get(self.pos..self.pos + word.len()) != Some(word.as_bytes()) {
+            return None;
+        }
+        self.pos += word.len();
+        Some(value)
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.bytes.get(self.pos) == Some(&byte);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> Option<String> {
+        parse_json(text)?.as_str().map(str::to_string)
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\"b\\c\/d\b\f\n\r\t""#).as_deref(), Some("a\"b\\c/d\u{8}\u{c}\n\r\t"));
+        assert_eq!(string(r#""\u0041\u00e9\u20AC""#).as_deref(), Some("Aé€"));
+        assert_eq!(string("\"héllo\"").as_deref(), Some("héllo"));
+        assert_eq!(string(r#""\x""#), None);
+        assert_eq!(string(r#""\u004""#), None);
+        assert_eq!(string(r#""\u+041""#), None);
+        assert_eq!(string("\"tab\there\""), None);
+        assert_eq!(string("\"unterminated"), None);
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(string(r#""\ud83d\ude00""#).as_deref(), Some("😀"));
+        assert_eq!(string(r#""\uD834\uDD1E""#).as_deref(), Some("𝄞"));
+        // Unpaired halves
+        assert_eq!(string(r#""\ud83d""#), None);
+        assert_eq!(string(r#""\ud83dx""#), None);
+        assert_eq!(string(r#""\ud83d\u0041""#), None);
+        assert_eq!(string(r#""\ude00""#), None);
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        let text = "quote \" backslash \\ newline \n bell \u{7} €";
+        assert_eq!(string(&json_string(text)).as_deref(), Some(text));
+        assert_eq!(json_escape("\u{1}"), "\\u0001");
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse_json(&nested(MAX_DEPTH + 1)).is_some());
+        assert!(parse_json(&nested(MAX_DEPTH + 2)).is_none());
+        // Deep enough to overflow the stack without the cap
+        assert!(parse_json(&"[".repeat(1_000_000)).is_none());
+        assert!(parse_json(&"{\"a\":".repeat(100_000)).is_none());
+    }
+
+    #[test]
+    fn numbers() {
+        for text in ["0", "-0", "42", "-7", "3.25", "1e10", "1E+2", "2.5e-3", "123456789012345678901234567890"] {
+            assert_eq!(parse_json(text), Some(JsonValue::Number(text.to_string())), "{}", text);
+        }
+        for text in ["01", "+1", "1.", ".5", "-", "1e", "1e+", "0x10", "1e999", "NaN", "Infinity"] {
+            assert_eq!(parse_json(text), None, "{}", text);
+        }
+        assert_eq!(parse_json("1.5").unwrap().as_f64(), Some(1.5));
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert!(parse_json(" {\"a\": [1, true, null]} \r\n").is_some());
+        assert_eq!(parse_json("{} x"), None);
+        assert_eq!(parse_json("[1] [2]"), None);
+        assert_eq!(parse_json("truex"), None);
+        assert_eq!(parse_json("[1,]"), None);
+        assert_eq!(parse_json("{\"a\":1,}"), None);
+        assert_eq!(parse_json(""), None);
+    }
+
+    #[test]
+    fn objects() {
+        let value = parse_json(r#"{"sub": "42", "aud": ["a", "b"], "n": {"x": false}}"#).unwrap();
+        assert_eq!(value.get("sub").and_then(JsonValue::as_str), Some("42"));
+        assert_eq!(value.get("n").and_then(|n| n.get("x")), Some(&JsonValue::Bool(false)));
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(value.to_json(), r#"{"sub":"42","aud":["a","b"],"n":{"x":false}}"#);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Built-in extension -> MIME type table, used unless overridden by a
+/// `mime_types` file or a route's `mime_type` directive
+const BUILTIN_TYPES: &[(&str, &str)] = &[
+    // Text
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("shtml", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("xml", "text/xml"),
+    ("ics", "text/calendar"),
+    ("vtt", "text/vtt"),
+    // Structured data / application
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("xhtml", "application/xhtml+xml"),
+    ("rtf", "application/rtf"),
+    ("doc", "application/msword"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("epub", "application/epub+zip"),
+    // Archives
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("bz2", "application/x-bzip2"),
+    ("xz", "application/x-xz"),
+    ("zst", "application/zstd"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("br", "application/x-brotli"),
+    ("jar", "application/java-archive"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("rpm", "application/x-rpm"),
+    ("iso", "application/x-iso9660-image"),
+    ("dmg", "application/x-apple-diskimage"),
+    ("exe", "application/vnd.microsoft.portable-executable"),
+    ("bin", "application/octet-stream"),
+    // Images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("svgz", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("apng", "image/apng"),
+    ("heic", "image/heic"),
+    // Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // Audio
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("aac", "audio/aac"),
+    ("m4a", "audio/mp4"),
+    ("weba", "audio/webm"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    // Video
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("mkv", "video/x-matroska"),
+    ("mpeg", "video/mpeg"),
+    ("mpg", "video/mpeg"),
+    ("ts", "video/mp2t"),
+    ("m3u8", "application/vnd.apple.mpegurl"),
+    ("3gp", "video/3gpp"),
+];
+
+/// Non-`text/*` types that are textual and should carry a charset
+const TEXTUAL_APPLICATION_TYPES: &[&str] = &[
+    "application/javascript",
+    "application/json",
+    "application/ld+json",
+    "application/manifest+json",
+    "application/xml",
+    "application/xhtml+xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "image/svg+xml",
+];
+
+/// Extension -> MIME type database
+#[derive(Debug, Clone)]
+pub struct MimeTypes {
+    by_extension: HashMap<String, String>,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl MimeTypes {
+    /// The built-in table of common web types
+    pub fn builtin() -> Self {
+        let by_extension = BUILTIN_TYPES
+            .iter()
+            .map(|(ext, mime)| (ext.to_string(), mime.to_string()))
+            .collect();
+        Self { by_extension }
+    }
+    
+    /// Merge an Apache (`type ext ext`) or nginx (`types { type ext ext; }`)
+    /// format `mime.types` file. Entries from the file win over built-ins.
+    /// Returns the number of extensions loaded.
+    pub fn load_file(&mut self, path: &str) -> io::Result<usize> {
+        let content = fs::read_to_string(path)?;
+        Ok(self.load_str(&content))
+    }
+    
+    pub fn load_str(&mut self, content: &str) -> usize {
+        let mut loaded = 0;
+        
+        for line in content.lines() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+            
+            // nginx syntax: strip the block wrapper and statement terminators
+            let line = line.replace(['{', '}', ';'], " ");
+            let mut parts = line.split_whitespace();
+            
+            let mime = match parts.next() {
+                Some(m) if m.contains('/') => m.to_ascii_lowercase(),
+                _ => continue, // "types" keyword or garbage
+            };
+            
+            for ext in parts {
+                let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+                if !ext.is_empty() {
+                    self.by_extension.insert(ext, mime.clone());
+                    loaded += 1;
+                }
+            }
+        }
+        
+        loaded
+    }
+    
+    /// Look up the MIME type for a file path by its extension
+    pub fn lookup(&self, file_path: &str) -> Option<&str> {
+        let ext = Path::new(file_path).extension()?.to_str()?.to_ascii_lowercase();
+        self.by_extension.get(&ext).map(|m| m.as_str())
+    }
+}
+
+/// Append `; charset=utf-8` to textual types that don't already declare one
+pub fn with_charset(mime: &str) -> String {
+    let base = mime.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let textual = base.starts_with("text/") || TEXTUAL_APPLICATION_TYPES.contains(&base.as_str());
+    
+    if textual && !mime.to_ascii_lowercase().contains("charset=") {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    }
+}
@@ -1,5 +1,23 @@
+mod chunked;
+mod compression;
+mod date;
+mod deflate;
+mod digest;
+mod json;
+mod mime;
+mod multipart;
 mod request;
 mod response;
+mod url;
 
-pub use request::HttpRequest;
-pub use response::HttpResponse;
+pub use chunked::{ChunkedDecoder, ChunkedError};
+pub use compression::{accepts_encoding, add_vary, compress_response};
+pub use date::{http_date, short_datetime, utc_components};
+pub use deflate::{crc32, crc32_update, gzip, zlib};
+pub use digest::{has_body_digest, verify_body_digest, BodyDigest};
+pub use json::{json_escape, json_string, parse_json, JsonValue};
+pub use mime::{with_charset, MimeTypes};
+pub use multipart::{multipart_boundary, MultipartError, MultipartReader, Part};
+pub use request::{BodyFile, HttpRequest, RemoteUser, RequestSession};
+pub use response::{registered_reason, status_text, BodyStream, HttpResponse, SessionUpdate};
+pub use url::{html_escape, percent_decode, percent_encode_path, percent_encode_segment};
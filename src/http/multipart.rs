@@ -0,0 +1,452 @@
+use std::io::{self, Read};
+
+use super::url::percent_decode_bytes;
+
+/// How much of the source is read at a time
+const READ_SIZE: usize = 64 * 1024;
+
+/// Longest header line accepted inside a part
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+/// Most headers accepted per part
+const MAX_HEADERS: usize = 32;
+
+/// Streaming `multipart/form-data` reader. Parts are visited in order with
+/// `next_part`; the body of the current part is pulled with `read_body`,
+/// so file contents never have to be held in memory.
+pub struct MultipartReader<R: Read> {
+    source: R,
+    buf: Vec<u8>,
+    /// `\r\n--boundary`
+    delimiter: Vec<u8>,
+    state: State,
+    eof: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before the first boundary
+    Preamble,
+    /// Headers of a part were returned; its body is next
+    Body,
+    /// The body was consumed up to the next boundary
+    Boundary,
+    /// Closing boundary seen
+    Done,
+}
+
+/// Headers of one part
+#[derive(Debug, Clone, Default)]
+pub struct Part {
+    /// `name` from Content-Disposition
+    pub name: String,
+    /// `filename*` (RFC 5987) if present, else `filename`, without any
+    /// directory components a client may have sent
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub enum MultipartError {
+    /// Body ended before the closing boundary
+    Truncated,
+    /// Bad part headers or boundary line
+    Malformed,
+    Io(io::Error),
+}
+
+impl From<io::Error> for MultipartError {
+    fn from(e: io::Error) -> Self {
+        MultipartError::Io(e)
+    }
+}
+
+/// The `boundary` parameter of a multipart Content-Type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let boundary = header_params(content_type)
+        .into_iter()
+        .find(|(key, _)| key == "boundary")
+        .map(|(_, value)| value)?;
+
+    // RFC 2046: 1 to 70 characters
+    if boundary.is_empty() || boundary.len() > 70 {
+        return None;
+    }
+    Some(boundary)
+}
+
+impl<R: Read> MultipartReader<R> {
+    pub fn new(source: R, boundary: &str) -> Self {
+        Self {
+            source,
+            // The first boundary may start the body; pretend it follows a
+            // line break like all the others
+            buf: b"\r\n".to_vec(),
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            state: State::Preamble,
+            eof: false,
+        }
+    }
+
+    /// Advance to the next part, skipping the preamble or whatever is left
+    /// of the current part's body. Returns None after the closing boundary.
+    pub fn next_part(&mut self) -> Result<Option<Part>, MultipartError> {
+        match self.state {
+            State::Done => return Ok(None),
+            State::Preamble | State::Body => {
+                self.read_body(|_| Ok(()))?;
+            }
+            State::Boundary => {}
+        }
+
+        // What follows the boundary: `--` closes, anything else is
+        // optional whitespace and a line break
+        self.fill_to(2)?;
+        if self.buf.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(None);
+        }
+        let padding = self.read_line()?;
+        if !padding.iter().all(|b| *b == b' ' || *b == b'\t') {
+            return Err(MultipartError::Malformed);
+        }
+
+        let mut part = Part::default();
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if part.headers.len() >= MAX_HEADERS {
+                return Err(MultipartError::Malformed);
+            }
+            let line = String::from_utf8_lossy(&line).to_string();
+            let (key, value) = line.split_once(':').ok_or(MultipartError::Malformed)?;
+            part.headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+
+        let header = |name: &str| {
+            part.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+        let disposition = header("Content-Disposition");
+        part.content_type = header("Content-Type");
+        if let Some(disposition) = disposition {
+            apply_disposition(&mut part, &disposition);
+        }
+
+        self.state = State::Body;
+        Ok(Some(part))
+    }
+
+    /// Pass the current part's body to `sink` in pieces, up to the next
+    /// boundary. Returns the body length.
+    pub fn read_body<F>(&mut self, mut sink: F) -> Result<u64, MultipartError>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        if self.state == State::Boundary || self.state == State::Done {
+            return Ok(0);
+        }
+
+        let mut total = 0u64;
+        loop {
+            if let Some(pos) = find(&self.buf, &self.delimiter) {
+                sink(&self.buf[..pos])?;
+                total += pos as u64;
+                self.buf.drain(..pos + self.delimiter.len());
+                self.state = State::Boundary;
+                return Ok(total);
+            }
+
+            // Everything except a possible partial delimiter at the end
+            // is body
+            let keep = self.delimiter.len() - 1;
+            if self.buf.len() > keep {
+                let emit = self.buf.len() - keep;
+                sink(&self.buf[..emit])?;
+                total += emit as u64;
+                self.buf.drain(..emit);
+            }
+
+            if !self.fill()? {
+                return Err(MultipartError::Truncated);
+            }
+        }
+    }
+
+    /// Read one CRLF-terminated line, without the line break
+    fn read_line(&mut self) -> Result<Vec<u8>, MultipartError> {
+        loop {
+            if let Some(pos) = find(&self.buf, b"\r\n") {
+                let line = self.buf[..pos].to_vec();
+                self.buf.drain(..pos + 2);
+                return Ok(line);
+            }
+            if self.buf.len() > MAX_HEADER_LINE {
+                return Err(MultipartError::Malformed);
+            }
+            if !self.fill()? {
+                return Err(MultipartError::Truncated);
+            }
+        }
+    }
+
+    /// Make sure at least `n` bytes are buffered
+    fn fill_to(&mut self, n: usize) -> Result<(), MultipartError> {
+        while self.buf.len() < n {
+            if !self.fill()? {
+                return Err(MultipartError::Truncated);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read more from the source. Returns false at end of input.
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let mut chunk = vec![0u8; READ_SIZE];
+        let n = loop {
+            match self.source.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if n == 0 {
+            self.eof = true;
+            return Ok(false);
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(true)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn apply_disposition(part: &mut Part, value: &str) {
+    let mut extended = None;
+    let mut plain = None;
+
+    for (key, val) in header_params(value) {
+        match key.as_str() {
+            "name" => part.name = val,
+            "filename" => plain = Some(val),
+            "filename*" => extended = decode_ext_value(&val),
+            _ => {}
+        }
+    }
+
+    // filename* wins when it could be decoded
+    part.filename = extended.or(plain).map(|name| strip_directories(&name));
+}
+
+/// `key=value` parameters after the first `;` of a header value, with
+/// quoted strings unescaped. Keys are lowercased.
+fn header_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    // Skip the leading type / disposition
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+
+    loop {
+        let mut key = String::new();
+        for c in chars.by_ref() {
+            if c == '=' || c == ';' {
+                break;
+            }
+            key.push(c);
+        }
+        let key = key.trim().to_ascii_lowercase();
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut val = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            val.push(escaped);
+                        }
+                    }
+                    '"' => break,
+                    _ => val.push(c),
+                }
+            }
+            // Skip to the next parameter
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+                val.push(c);
+            }
+            val = val.trim().to_string();
+        }
+
+        if !key.is_empty() {
+            params.push((key, val));
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+    }
+
+    params
+}
+
+/// Decode an RFC 5987 `charset'language'percent-encoded` value.
+/// UTF-8 and ISO-8859-1 are understood.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut fields = value.splitn(3, '\'');
+    let charset = fields.next()?.trim().to_ascii_lowercase();
+    let _language = fields.next()?;
+    let bytes = percent_decode_bytes(fields.next()?);
+
+    match charset.as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        "iso-8859-1" => Some(bytes.iter().map(|&b| b as char).collect()),
+        _ => None,
+    }
+}
+
+/// Some clients send the full local path; keep only the last component
+fn strip_directories(name: &str) -> String {
+    name.rsplit(['/', '\\']).next().unwrap_or("").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out at most `step` bytes per read, so delimiters and header
+    /// lines straddle reads
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        hello\r\n--XyZ \t\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"C:\\\\tmp\\\\a.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        line one\r\n-XyZ --Xy\r\n\r\n--XyZ--\r\nepilogue";
+
+    /// Every part's headers and body, or the error that stopped the reader
+    fn parts(data: &[u8], step: usize) -> Result<Vec<(Part, Vec<u8>)>, MultipartError> {
+        let mut reader = MultipartReader::new(Trickle { data, step }, "XyZ");
+        let mut parts = Vec::new();
+        while let Some(part) = reader.next_part()? {
+            let mut body = Vec::new();
+            reader.read_body(|piece| {
+                body.extend_from_slice(piece);
+                Ok(())
+            })?;
+            parts.push((part, body));
+        }
+        Ok(parts)
+    }
+
+    #[test]
+    fn boundary_split_across_reads() {
+        for step in [1, 2, 3, 5, 7, 16, BODY.len()] {
+            let parts = parts(BODY, step).unwrap();
+            assert_eq!(parts.len(), 2, "step {}", step);
+            assert_eq!(parts[0].0.name, "title");
+            assert_eq!(parts[0].1, b"hello");
+            assert_eq!(parts[1].0.name, "file");
+            assert_eq!(parts[1].0.filename.as_deref(), Some("a.txt"));
+            assert_eq!(parts[1].0.content_type.as_deref(), Some("text/plain"));
+            assert_eq!(parts[1].1, b"line one\r\n-XyZ --Xy\r\n");
+        }
+    }
+
+    #[test]
+    fn unread_bodies_are_skipped() {
+        let mut reader = MultipartReader::new(Trickle { data: BODY, step: 3 }, "XyZ");
+        assert_eq!(reader.next_part().unwrap().unwrap().name, "title");
+        assert_eq!(reader.next_part().unwrap().unwrap().name, "file");
+        assert!(reader.next_part().unwrap().is_none());
+        assert!(reader.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_closing_boundary() {
+        // The last delimiter lacks its `--`
+        let open = b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--XyZ\r\n";
+        assert!(matches!(parts(open, 4), Err(MultipartError::Truncated)));
+        // No delimiter after the last part at all
+        let unterminated = b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n";
+        assert!(matches!(parts(unterminated, 4), Err(MultipartError::Truncated)));
+    }
+
+    #[test]
+    fn truncated_body() {
+        for cut in [3, 20, 60, BODY.len() - 20] {
+            let result = parts(&BODY[..cut], 5);
+            assert!(matches!(result, Err(MultipartError::Truncated)), "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn malformed_parts() {
+        // Junk after a boundary
+        let junk = b"--XyZjunk\r\n\r\n\r\n--XyZ--";
+        assert!(matches!(parts(junk, 64), Err(MultipartError::Malformed)));
+        // A header line without a colon
+        let header = b"--XyZ\r\nno colon\r\n\r\nx\r\n--XyZ--";
+        assert!(matches!(parts(header, 64), Err(MultipartError::Malformed)));
+        // Too many headers
+        let many = format!("--XyZ\r\n{}\r\nx\r\n--XyZ--", "X-A: 1\r\n".repeat(MAX_HEADERS + 1));
+        assert!(matches!(parts(many.as_bytes(), 64), Err(MultipartError::Malformed)));
+    }
+
+    #[test]
+    fn extended_filename_wins() {
+        let body = "--XyZ\r\nContent-Disposition: form-data; name=\"f\"; filename=\"plain.txt\"; \
+                    filename*=UTF-8''%E2%82%AC%20rates.txt\r\n\r\n\r\n--XyZ--";
+        let parts = parts(body.as_bytes(), 64).unwrap();
+        assert_eq!(parts[0].0.filename.as_deref(), Some("€ rates.txt"));
+        assert_eq!(parts[0].1, b"");
+    }
+
+    #[test]
+    fn boundary_parameter() {
+        assert_eq!(multipart_boundary("multipart/form-data; boundary=abc").as_deref(), Some("abc"));
+        assert_eq!(multipart_boundary("multipart/form-data; charset=utf-8; boundary=\"a b;c\"").as_deref(), Some("a b;c"));
+        assert_eq!(multipart_boundary("multipart/form-data"), None);
+        assert_eq!(multipart_boundary("multipart/form-data; boundary="), None);
+        assert_eq!(multipart_boundary(&format!("multipart/form-data; boundary={}", "b".repeat(71))), None);
+    }
+}
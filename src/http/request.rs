@@ -1,4 +1,7 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::ChunkedDecoder;
 
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
@@ -8,12 +11,75 @@ pub struct HttpRequest {
     pub version: String,
     pub headers: HashMap<String, String>,
     pub body: Vec<u8>,
+    /// Body that was streamed to disk instead of kept in `body`
+    pub body_file: Option<BodyFile>,
+    /// The client's session, resolved by the server before routing
+    pub session: Option<RequestSession>,
+    /// Who the request authenticated as on a protected route
+    pub remote_user: Option<RemoteUser>,
+}
+
+/// An authenticated user, for `REMOTE_USER` and `AUTH_TYPE`
+#[derive(Debug, Clone)]
+pub struct RemoteUser {
+    pub name: String,
+    /// `Basic`, `Digest` or `Bearer`
+    pub auth_type: &'static str,
+    /// Token claims picked by `auth_jwt_claims`, for `JWT_<NAME>`
+    pub claims: Vec<(String, String)>,
+}
+
+/// The session a request belongs to, as handlers (CGI) get to see it
+#[derive(Debug, Clone, Default)]
+pub struct RequestSession {
+    pub id: String,
+    pub data: HashMap<String, String>,
+}
+
+/// A request body spooled to a temporary file while it was received
+#[derive(Debug, Clone)]
+pub struct BodyFile {
+    pub path: PathBuf,
+    pub len: u64,
+    /// SHA-256 of the body, computed while it was written
+    pub sha256: [u8; 32],
+    /// MD5 of the body, for `Content-MD5` checks
+    pub md5: [u8; 16],
 }
 
 impl HttpRequest {
     /// Parse HTTP request from buffer
     /// Returns None if request is incomplete
     pub fn parse(buffer: &[u8]) -> Option<Self> {
+        let (mut request, body_start) = Self::parse_head(buffer)?;
+        
+        // Chunked framing wins over Content-Length (RFC 9112 §6.3), as it
+        // does for spooled uploads
+        if request.is_chunked() {
+            let mut decoder = ChunkedDecoder::new();
+            decoder.feed(&buffer[body_start..], &mut request.body).ok()?;
+            return decoder.is_done().then_some(request);
+        }
+        
+        // Handle Content-Length body
+        if let Some(len_str) = request.headers.get("Content-Length") {
+            let expected: usize = len_str.parse().ok()?;
+            let available = buffer.len().saturating_sub(body_start);
+            
+            if available < expected {
+                return None; // Need more data
+            }
+            
+            request.body = buffer[body_start..body_start + expected].to_vec();
+        }
+        
+        Some(request)
+    }
+    
+    /// Parse only the request line and headers. Returns the request with an
+    /// empty body and the offset where the body starts, or None if the
+    /// headers are incomplete.
+    pub fn parse_head(buffer: &[u8]) -> Option<(Self, usize)> {
         // Find end of headers
         let headers_end = find_header_end(buffer)?;
         let header_section = &buffer[..headers_end];
@@ -29,53 +95,51 @@ impl HttpRequest {
         // Parse headers
         let headers = parse_headers(lines);
         
-        let body_start = headers_end + 4;
-        
-        // Handle Content-Length body
-        if let Some(len_str) = headers.get("Content-Length") {
-            let expected: usize = len_str.parse().ok()?;
-            let available = buffer.len().saturating_sub(body_start);
-            
-            if available < expected {
-                return None; // Need more data
-            }
-            
-            let body = buffer[body_start..body_start + expected].to_vec();
-            
-            return Some(HttpRequest {
-                method,
-                path,
-                query,
-                version,
-                headers,
-                body,
-            });
-        }
-        
-        // Handle chunked encoding
-        if let Some(te) = headers.get("Transfer-Encoding") {
-            if te.to_lowercase().contains("chunked") {
-                let body = parse_chunked(&buffer[body_start..])?;
-                return Some(HttpRequest {
-                    method,
-                    path,
-                    query,
-                    version,
-                    headers,
-                    body,
-                });
-            }
-        }
-        
-        // No body
-        Some(HttpRequest {
+        let request = HttpRequest {
             method,
             path,
             query,
             version,
             headers,
             body: Vec::new(),
-        })
+            body_file: None,
+            session: None,
+            remote_user: None,
+        };
+        Some((request, headers_end + 4))
+    }
+    
+    /// Whether the body uses chunked transfer encoding
+    pub fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding")
+            .is_some_and(|te| te.to_lowercase().contains("chunked"))
+    }
+    
+    /// Whether the request sends both `Transfer-Encoding` and
+    /// `Content-Length`, which peers may frame differently
+    pub fn has_conflicting_framing(&self) -> bool {
+        self.header("Transfer-Encoding").is_some() && self.header("Content-Length").is_some()
+    }
+    
+    /// Declared Content-Length, if any
+    pub fn content_length(&self) -> Option<u64> {
+        self.header("Content-Length").and_then(|v| v.trim().parse().ok())
+    }
+    
+    /// Size of the body, whether held in memory or spooled to disk
+    pub fn body_len(&self) -> u64 {
+        match &self.body_file {
+            Some(file) => file.len,
+            None => self.body.len() as u64,
+        }
+    }
+    
+    /// Case-insensitive header lookup
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
     }
 }
 
@@ -108,36 +172,33 @@ fn parse_headers<'a>(lines: impl Iterator<Item = &'a str>) -> HashMap<String, St
     headers
 }
 
-fn parse_chunked(data: &[u8]) -> Option<Vec<u8>> {
-    let mut body = Vec::new();
-    let mut pos = 0;
-    
-    loop {
-        if pos >= data.len() {
-            return None; // Incomplete
-        }
-        
-        // Find chunk size line end
-        let remaining = &data[pos..];
-        let line_end = remaining.windows(2).position(|w| w == b"\r\n")?;
-        
-        // Parse hex size
-        let size_str = std::str::from_utf8(&remaining[..line_end]).ok()?;
-        let size = usize::from_str_radix(size_str.trim(), 16).ok()?;
-        
-        pos += line_end + 2;
-        
-        if size == 0 {
-            break; // End of chunks
-        }
-        
-        if pos + size + 2 > data.len() {
-            return None; // Need more data
-        }
-        
-        body.extend_from_slice(&data[pos..pos + size]);
-        pos += size + 2; // Skip chunk data + CRLF
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_length_body() {
+        let request = HttpRequest::parse(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhelloEXTRA").unwrap();
+        assert_eq!(request.body, b"hello");
+        assert!(HttpRequest::parse(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhel").is_none());
+    }
+
+    #[test]
+    fn chunked_body() {
+        let raw = b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n5;name=value\r\nhello\r\n1\r\n!\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        assert_eq!(HttpRequest::parse(raw).unwrap().body, b"hello!");
+        // Incomplete until the empty line after the trailers
+        assert!(HttpRequest::parse(&raw[..raw.len() - 2]).is_none());
+    }
+
+    #[test]
+    fn chunked_wins_over_content_length() {
+        let raw = b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let request = HttpRequest::parse(raw).unwrap();
+        assert!(request.has_conflicting_framing());
+        assert_eq!(request.body, b"hello");
+
+        let plain = HttpRequest::parse(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 0\r\n\r\n").unwrap();
+        assert!(!plain.has_conflicting_framing());
     }
-    
-    Some(body)
 }
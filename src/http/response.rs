@@ -1,11 +1,44 @@
 use std::collections::HashMap;
+use std::fmt;
+
+/// Produces a response body piece by piece, for bodies that shouldn't be
+/// built in memory up front. Streamed bodies are sent chunked.
+pub trait BodyStream {
+    /// Next piece of the body, or None once everything has been produced
+    fn next_chunk(&mut self) -> Option<Vec<u8>>;
+}
 
-#[derive(Debug, Clone)]
 pub struct HttpResponse {
     pub status_code: u16,
     pub status_text: String,
     pub headers: HashMap<String, String>,
     pub body: Vec<u8>,
+    /// When set, replaces `body` and is sent with chunked encoding
+    pub stream: Option<Box<dyn BodyStream>>,
+    /// Changes a handler wants made to the request's session
+    pub session_updates: Vec<SessionUpdate>,
+}
+
+/// A change to the session asked for by a handler (CGI `X-Session-*`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionUpdate {
+    Set(String, String),
+    /// Drop one key
+    Remove(String),
+    Destroy,
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status_code", &self.status_code)
+            .field("status_text", &self.status_text)
+            .field("headers", &self.headers)
+            .field("body", &self.body.len())
+            .field("stream", &self.stream.is_some())
+            .field("session_updates", &self.session_updates)
+            .finish()
+    }
 }
 
 impl HttpResponse {
@@ -15,6 +48,8 @@ impl HttpResponse {
             status_text: status_text.to_string(),
             headers: HashMap::new(),
             body: Vec::new(),
+            stream: None,
+            session_updates: Vec::new(),
         }
     }
     
@@ -22,6 +57,19 @@ impl HttpResponse {
         self.headers.insert(key.to_string(), value.to_string());
     }
     
+    /// Case-insensitive header lookup
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    
+    /// Remove a header regardless of the case it was set with
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|k, _| !k.eq_ignore_ascii_case(name));
+    }
+    
     pub fn set_body(&mut self, text: &str) {
         self.body = text.as_bytes().to_vec();
     }
@@ -30,6 +78,11 @@ impl HttpResponse {
         self.body = bytes;
     }
     
+    pub fn set_body_stream(&mut self, stream: Box<dyn BodyStream>) {
+        self.body.clear();
+        self.stream = Some(stream);
+    }
+    
     /// Convert to wire format
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut output = Vec::new();
@@ -44,8 +97,10 @@ impl HttpResponse {
             output.extend_from_slice(format!("{}: {}\r\n", key, value).as_bytes());
         }
         
-        // Content-Length
-        if !self.headers.contains_key("Content-Length") {
+        // Content-Length (streamed bodies are chunked instead)
+        if self.stream.is_some() {
+            output.extend_from_slice(b"Transfer-Encoding: chunked\r\n");
+        } else if !self.headers.contains_key("Content-Length") {
             output.extend_from_slice(
                 format!("Content-Length: {}\r\n", self.body.len()).as_bytes()
             );
@@ -96,3 +151,84 @@ impl HttpResponse {
         response
     }
 }
+
+/// Reason phrase for a status code: the registered one, or the name of
+/// its class for codes without one
+pub fn status_text(code: u16) -> &'static str {
+    registered_reason(code).unwrap_or(match code / 100 {
+        1 => "Informational",
+        2 => "Success",
+        3 => "Redirection",
+        4 => "Client Error",
+        _ => "Server Error",
+    })
+}
+
+/// The reason phrase registered for `code` (RFC 9110 and the IANA HTTP
+/// status code registry), or None if the code is unassigned
+pub fn registered_reason(code: u16) -> Option<&'static str> {
+    Some(match code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Content",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => return None,
+    })
+}
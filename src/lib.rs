@@ -1,4 +1,5 @@
 pub mod config;
+pub mod crypto;
 pub mod http;
 pub mod handlers;
 pub mod network;
@@ -3,6 +3,14 @@ use std::net::TcpStream;
 use std::time::Instant;
 use std::io::{Read, Write};
 
+use crate::http::{BodyStream, HttpResponse};
+
+use super::spool::BodySpool;
+
+/// Slowest average rate, in bytes per second, an upload body may arrive at
+/// once the regular request timeout has passed
+const MIN_BODY_RATE: u64 = 8 * 1024;
+
 /// Connection state machine
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConnState {
@@ -21,10 +29,16 @@ pub struct ClientConnection {
     
     // Read side
     pub read_buffer: Vec<u8>,
+    /// Upload body being written to disk as it arrives
+    pub body_spool: Option<BodySpool>,
+    /// Response decided before the request was fully read (e.g. 413)
+    pub early_response: Option<HttpResponse>,
     
     // Write side  
     pub write_buffer: Vec<u8>,
     pub bytes_written: usize,
+    /// Remaining body of a streamed response, pulled as the socket drains
+    pub body_stream: Option<Box<dyn BodyStream>>,
     
     // Timing
     pub connected_at: Instant,
@@ -45,8 +59,11 @@ impl ClientConnection {
             stream,
             state: ConnState::Reading,
             read_buffer: Vec::with_capacity(4096),
+            body_spool: None,
+            early_response: None,
             write_buffer: Vec::new(),
             bytes_written: 0,
+            body_stream: None,
             connected_at: now,
             last_activity: now,
             request_started_at: None,  // No request started yet
@@ -94,7 +111,7 @@ impl ClientConnection {
     /// Try to write data to socket (non-blocking)
     /// Returns: Ok(true) if all data written, Ok(false) if more to write
     pub fn try_write(&mut self) -> Result<bool, ConnectionError> {
-        if self.bytes_written >= self.write_buffer.len() {
+        if self.bytes_written >= self.write_buffer.len() && !self.refill_from_stream() {
             // Nothing to write
             return Ok(true);
         }
@@ -111,7 +128,8 @@ impl ClientConnection {
                 self.last_activity = Instant::now();
                 
                 // Check if we're done
-                let complete = self.bytes_written >= self.write_buffer.len();
+                let complete = self.bytes_written >= self.write_buffer.len()
+                    && self.body_stream.is_none();
                 Ok(complete)
             }
             Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -128,18 +146,88 @@ impl ClientConnection {
         }
     }
     
+    /// Queue an interim response (`100 Continue`) to go out through the
+    /// write path while the request body is still being read
+    pub fn queue_interim(&mut self, data: &[u8]) {
+        self.write_buffer.extend_from_slice(data);
+    }
+    
+    /// Whether an interim response is still waiting for the socket
+    pub fn interim_pending(&self) -> bool {
+        self.state == ConnState::Reading && self.bytes_written < self.write_buffer.len()
+    }
+    
+    /// Write more of the pending interim response.
+    /// Returns: Ok(true) once it has all been sent
+    pub fn flush_interim(&mut self) -> Result<bool, ConnectionError> {
+        let done = self.try_write()?;
+        if done {
+            self.write_buffer.clear();
+            self.bytes_written = 0;
+        }
+        Ok(done)
+    }
+    
     /// Queue response for writing
     pub fn queue_response(&mut self, data: Vec<u8>) {
-        self.write_buffer = data;
+        // An interim response cut off mid-write must be finished first
+        let mut pending = self.write_buffer.split_off(self.bytes_written.min(self.write_buffer.len()));
+        self.write_buffer = if pending.is_empty() {
+            data
+        } else {
+            pending.extend_from_slice(&data);
+            pending
+        };
         self.bytes_written = 0;
+        self.body_stream = None;
         self.state = ConnState::Writing;
     }
     
+    /// Queue response head, followed by a body produced on demand
+    pub fn queue_streaming_response(&mut self, head: Vec<u8>, stream: Box<dyn BodyStream>) {
+        self.queue_response(head);
+        self.body_stream = Some(stream);
+    }
+    
+    /// Load the next chunk of a streamed body into the write buffer,
+    /// framed for chunked transfer encoding. Returns false if there is
+    /// no stream left to pull from.
+    fn refill_from_stream(&mut self) -> bool {
+        let stream = match self.body_stream.as_mut() {
+            Some(s) => s,
+            None => return false,
+        };
+        
+        loop {
+            match stream.next_chunk() {
+                Some(chunk) if chunk.is_empty() => continue,
+                Some(chunk) => {
+                    let mut framed = format!("{:x}\r\n", chunk.len()).into_bytes();
+                    framed.extend_from_slice(&chunk);
+                    framed.extend_from_slice(b"\r\n");
+                    self.write_buffer = framed;
+                    break;
+                }
+                None => {
+                    self.write_buffer = b"0\r\n\r\n".to_vec();
+                    self.body_stream = None;
+                    break;
+                }
+            }
+        }
+        
+        self.bytes_written = 0;
+        true
+    }
+    
     /// Reset for next request (keep-alive)
     pub fn reset_for_next_request(&mut self) {
         self.read_buffer.clear();
+        self.body_spool = None;
+        self.early_response = None;
         self.write_buffer.clear();
         self.bytes_written = 0;
+        self.body_stream = None;
         self.state = ConnState::Reading;
         self.requests_handled += 1;
         // IMPORTANT: Reset request timeout for next request
@@ -153,6 +241,13 @@ impl ClientConnection {
     
     /// Check if current REQUEST is taking too long (incomplete request timeout)
     pub fn is_request_timeout(&self, timeout_secs: u64) -> bool {
+        // Large uploads legitimately take longer: every byte received buys
+        // more time, so a body trickled in just fast enough to dodge the
+        // idle timeout still runs out
+        if let Some(spool) = &self.body_spool {
+            let allowed = timeout_secs + spool.received() / MIN_BODY_RATE;
+            return spool.elapsed().as_secs() > allowed;
+        }
         if let Some(started) = self.request_started_at {
             // Only timeout if we're still reading (incomplete request)
             if self.state == ConnState::Reading {
@@ -164,7 +259,7 @@ impl ClientConnection {
     
     /// Check if write is complete
     pub fn write_complete(&self) -> bool {
-        self.bytes_written >= self.write_buffer.len()
+        self.bytes_written >= self.write_buffer.len() && self.body_stream.is_none()
     }
     
     /// Check if we want to read
@@ -139,8 +139,7 @@ impl Epoll {
         }
         
         let mut results = Vec::with_capacity(count as usize);
-        for i in 0..count as usize {
-            let ev = &raw_events[i];
+        for ev in raw_events.iter().take(count as usize) {
             let flags = ev.events;
             
             results.push(SocketEvent {
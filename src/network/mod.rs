@@ -3,9 +3,11 @@ mod epoll_wrapper;
 mod listener;
 mod router;
 mod connection;
+mod spool;
 pub mod server;
 
 pub use epoll_wrapper::{Epoll, Interest, SocketEvent};
 pub use listener::{create_listener, set_nonblocking};
-pub use router::{find_route, route_request};
+pub use router::{authenticate_request, error_response, find_route, route_request, session_mode, upload_target, UploadTarget};
 pub use connection::{ClientConnection, ConnState, ConnectionError};
+pub use spool::{BodySpool, SpoolError};
@@ -1,6 +1,11 @@
-use crate::config::{RouteConfig, ServerConfig, VHost};
-use crate::http::{HttpRequest, HttpResponse};
+use crate::config::{match_ext_rule, Expires, RouteConfig, ServerConfig, SessionMode, VHost};
+use crate::handlers::AuthError;
+use crate::http::{add_vary, compress_response, has_body_digest, http_date, percent_decode, status_text, verify_body_digest, with_charset, BodyDigest, HttpRequest, HttpResponse, RemoteUser};
 use std::path::{Path, PathBuf, Component};
+use std::time::{Duration, SystemTime};
+
+/// Give up after this many try_files internal redirects
+const MAX_INTERNAL_REDIRECTS: u8 = 10;
 
 /// Sanitize and validate a path to prevent directory traversal attacks
 fn sanitize_path(base: &str, user_path: &str) -> Option<PathBuf> {
@@ -59,11 +64,11 @@ fn url_decode(s: &str) -> String {
         if c == '%' {
             // Try to read two hex digits
             let hex: String = chars.by_ref().take(2).collect();
-            if hex.len() == 2 {
-                if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                    result.push(byte as char);
-                    continue;
-                }
+            if hex.len() == 2
+                && let Ok(byte) = u8::from_str_radix(&hex, 16)
+            {
+                result.push(byte as char);
+                continue;
             }
             // Invalid encoding, keep as-is
             result.push('%');
@@ -125,145 +130,466 @@ pub fn route_request(
     }
 
     // Check body size limit EARLY (before any processing)
-    if request.body.len() > config.client_body_size_limit {
+    if request.body_len() > config.client_body_size_limit as u64 {
         return error_response(413, &config.error_path, "Payload Too Large");
     }
 
-    let (routes, error_path) = if !config.vhosts.is_empty() {
-        if let Some(vhost) = find_vhost(request, &config.vhosts) {
-            (&vhost.routes, &vhost.error_path)
-        } else {
-            (&config.routes, &config.error_path)
+    let (routes, error_path) = resolve_routes(request, config);
+    
+    // A body that doesn't match the digest the client sent was corrupted
+    // on the way: refuse it before anything stores it
+    let stores_body = upload_target(request, config).is_some();
+    let digest = (stores_body || has_body_digest(request)).then(|| BodyDigest::of(request));
+    if let Some(digest) = &digest
+        && let Err(header) = verify_body_digest(request, digest)
+    {
+        eprintln!("[router] {} does not match the body of {} {}", header, request.method, request.path);
+        return error_response(400, error_path, "Bad Request");
+    }
+    
+    let mut response = dispatch(request, config, routes, error_path, 0);
+    
+    // Let the client confirm what was stored
+    if stores_body
+        && let Some(digest) = &digest
+        && (200..300).contains(&response.status_code)
+    {
+        digest.set_headers(&mut response);
+    }
+    response
+}
+
+/// Where an upload request's body should be spooled while it is read
+pub struct UploadTarget {
+    pub dir: String,
+    pub limit: u64,
+    pub error_path: String,
+}
+
+/// Decide from the request head alone whether the body is going to be
+/// stored (POST to an upload route, or PUT), so it can be streamed to disk
+/// instead of buffered
+pub fn upload_target(request: &HttpRequest, config: &ServerConfig) -> Option<UploadTarget> {
+    if !is_path_safe(&request.path) {
+        return None;
+    }
+    
+    let (routes, error_path) = resolve_routes(request, config);
+    let route = find_route(request, routes)?;
+    
+    let tus = route.tus && crate::handlers::is_tus_request(request);
+    if !tus && !route.methods.iter().any(|m| m.eq_ignore_ascii_case(&request.method)) {
+        return None;
+    }
+    
+    let stores_body = if request.method.eq_ignore_ascii_case("POST") {
+        route.path.contains("/upload") && !(route.trash && crate::handlers::is_restore_request(request))
+    } else if request.method.eq_ignore_ascii_case("PATCH") {
+        route.tus
+    } else {
+        request.method.eq_ignore_ascii_case("PUT") && route.cgi.is_none()
+    };
+    if !stores_body || route.redirect.is_some() {
+        return None;
+    }
+    
+    Some(UploadTarget {
+        dir: route.root.clone(),
+        limit: config.client_body_size_limit as u64,
+        error_path: error_path.to_string(),
+    })
+}
+
+/// Whether the request gets a session: the route's `sessions`, else its
+/// vhost's, else the server's
+pub fn session_mode(request: &HttpRequest, config: &ServerConfig) -> SessionMode {
+    let vhost = find_vhost(request, &config.vhosts);
+    let routes = vhost.map_or(config.routes.as_slice(), |v| v.routes.as_slice());
+    find_route(request, routes)
+        .and_then(|route| route.sessions)
+        .or(vhost.and_then(|v| v.sessions))
+        .unwrap_or(config.session.mode)
+}
+
+/// Check the credentials the request's route (or its vhost) asks for with
+/// `auth_basic` / `auth_digest`: the user it authenticated as, None on an
+/// open route, or the response to send instead
+pub fn authenticate_request(request: &HttpRequest, config: &ServerConfig) -> Result<Option<RemoteUser>, Box<HttpResponse>> {
+    let vhost = find_vhost(request, &config.vhosts);
+    let (routes, error_path) = resolve_routes(request, config);
+    let Some(route) = find_route(request, routes) else {
+        return Ok(None);
+    };
+    let auth = match vhost {
+        Some(vhost) => route.auth.or(&vhost.auth),
+        None => route.auth.clone(),
+    };
+    
+    crate::handlers::check_credentials(request, &auth).map_err(|e| match e {
+        AuthError::Unauthorized(challenge) => {
+            let mut response = error_response(401, error_path, "Unauthorized");
+            response.set_header("WWW-Authenticate", &challenge);
+            Box::new(response)
+        }
+        AuthError::Forbidden(challenge) => {
+            let mut response = error_response(403, error_path, "Forbidden");
+            response.set_header("WWW-Authenticate", &challenge);
+            Box::new(response)
         }
+        AuthError::Misconfigured => Box::new(error_response(500, error_path, "Internal Server Error")),
+    })
+}
+
+/// Pick the vhost's routes and error pages, falling back to the defaults
+fn resolve_routes<'a>(request: &HttpRequest, config: &'a ServerConfig) -> (&'a [RouteConfig], &'a str) {
+    if let Some(vhost) = find_vhost(request, &config.vhosts) {
+        (&vhost.routes, &vhost.error_path)
     } else {
         (&config.routes, &config.error_path)
+    }
+}
+
+/// Match a route and run its handler, followed by the compression stage.
+/// `redirects` counts internal redirects (try_files) taken so far.
+fn dispatch(
+    request: &HttpRequest,
+    config: &ServerConfig,
+    routes: &[RouteConfig],
+    error_path: &str,
+    redirects: u8,
+) -> HttpResponse {
+    let route = match find_route(request, routes) {
+        Some(route) => route,
+        None => return error_response(404, error_path, "Not Found"),
     };
     
-    let matched = find_route(request, routes);
+    let mut response = handle_route(request, config, routes, route, error_path, redirects);
     
-    match matched {
-        Some(route) => {
-            // Check method
-            if !route.methods.iter().any(|m| m.eq_ignore_ascii_case(&request.method)) {
-                return error_response(405, error_path, "Method Not Allowed");
-            }
-            
-            // Handle redirect
-            if let Some(target) = &route.redirect {
-                let mut resp = HttpResponse::new(302, "Found");
-                resp.set_header("Location", target);
-                return resp;
-            }
-            
-            // Handle upload
-            if route.path.contains("/upload") {
-                if request.method.eq_ignore_ascii_case("POST") {
-                    // Double check size limit for uploads and return proper error page
-                    if request.body.len() > config.client_body_size_limit {
-                        return error_response(413, error_path, "Payload Too Large");
-                    }
-                    return crate::handlers::upload_file(request, config.client_body_size_limit, error_path);
-                } else if request.method.eq_ignore_ascii_case("DELETE") {
-                    return crate::handlers::delete_file(request);
-                }
-            }
-            
-            // Handle CGI
-            if let Some(_cgi) = &route.cgi {
-                // Extract script name from URL path
-                // e.g., /cgi/test.py -> test.py
-                let after_route = request.path
-                    .strip_prefix(&route.path)
-                    .unwrap_or(&request.path)
-                    .trim_start_matches('/');
-                
-                // Handle empty script name
-                if after_route.is_empty() {
-                    return error_response(404, error_path, "Not Found");
-                }
-                
-                // SECURITY: Validate CGI script path
-                let script_path = match sanitize_path(&route.root, after_route) {
-                    Some(safe_path) => safe_path.to_string_lossy().to_string(),
-                    None => return error_response(403, error_path, "Forbidden"),
-                };
-                
-                let path_info = request.path.clone();
-                return crate::handlers::run_cgi(&script_path, &path_info, request);
-            }
-            
-            // Build file path SAFELY
-            let file_path = if route.path == "/" {
-                let req_path = request.path.trim_start_matches('/');
-                if req_path.is_empty() {
-                    if let Some(df) = &route.default_file {
-                        format!("{}/{}", route.root, df)
-                    } else {
-                        route.root.clone()
-                    }
-                } else {
-                    // SECURITY: Sanitize the path
-                    match sanitize_path(&route.root, req_path) {
-                        Some(safe_path) => safe_path.to_string_lossy().to_string(),
-                        None => return error_response(403, error_path, "Forbidden"),
-                    }
-                }
-            } else {
-                let after = request.path.strip_prefix(&route.path)
-                    .unwrap_or("")
-                    .trim_start_matches('/');
-                
-                if after.is_empty() {
-                    route.root.clone()
-                } else {
-                    // SECURITY: Sanitize the path
-                    match sanitize_path(&route.root, after) {
-                        Some(safe_path) => safe_path.to_string_lossy().to_string(),
-                        None => return error_response(403, error_path, "Forbidden"),
-                    }
-                }
-            };
-            
-            let path_obj = Path::new(&file_path);
-            
-            // Serve file
-            if path_obj.is_file() {
-                match crate::handlers::serve_file(&file_path) {
-                    Ok(content) => {
-                        let mut response = HttpResponse::ok();
-                        response.set_header("Content-Type", get_content_type(&file_path));
-                        response.set_body_bytes(content);
-                        return response;
-                    }
-                    Err(_) => {
-                        return error_response(404, error_path, "Not Found");
-                    }
-                }
-            }
-            
-            // Handle directory
-            if path_obj.is_dir() {
-                if let Some(df) = &route.default_file {
-                    let default_path = format!("{}/{}", file_path, df);
-                    if let Ok(content) = crate::handlers::serve_file(&default_path) {
-                        let mut response = HttpResponse::ok();
-                        response.set_header("Content-Type", "text/html");
-                        response.set_body_bytes(content);
-                        return response;
-                    }
-                }
-                
-                if route.autoindex {
-                    return crate::handlers::list_directory(&file_path, &request.path, &route.root);
-                }
-                
-                return error_response(403, error_path, "Forbidden");
-            }
-            
-            error_response(404, error_path, "Not Found")
+    // Compression stage: runs on whatever the handler produced
+    if route.gzip {
+        compress_response(request, &mut response, route.gzip_min_length);
+    }
+    
+    response
+}
+
+/// Dispatch a request to the handler for its matched route
+fn handle_route(
+    request: &HttpRequest,
+    config: &ServerConfig,
+    routes: &[RouteConfig],
+    route: &RouteConfig,
+    error_path: &str,
+    redirects: u8,
+) -> HttpResponse {
+    // On a route with both, OPTIONS is answered by WebDAV, with the tus
+    // discovery headers added, so clients of either protocol see theirs
+    let shared_options = route.tus && route.webdav && request.method.eq_ignore_ascii_case("OPTIONS");
+    
+    // Resumable uploads bring their own set of methods
+    if route.tus && !shared_options && crate::handlers::is_tus_request(request) {
+        return crate::handlers::handle_tus(request, route, config.client_body_size_limit as u64);
+    }
+    
+    // The server's own state under the root (trash, upload temp files)
+    // doesn't exist as far as clients are concerned
+    if percent_decode(&request.path).split('/').any(crate::handlers::is_internal_name) {
+        return error_response(404, error_path, "Not Found");
+    }
+    
+    // WebDAV brings its own methods on top of the route's
+    if route.webdav && crate::handlers::is_webdav_method(&request.method) {
+        let context = crate::handlers::DavContext {
+            route,
+            map_path: &|uri| map_to_root(route, uri),
+            content_type: &|file_path| get_content_type(file_path, route, config),
+        };
+        let mut response = crate::handlers::handle_webdav(request, &context)
+            .unwrap_or_else(|code| error_response(code, error_path, status_text(code)));
+        if shared_options {
+            crate::handlers::add_tus_options(&mut response, config.client_body_size_limit as u64);
         }
-        None => error_response(404, error_path, "Not Found")
+        return response;
+    }
+    
+    // Check method
+    if !route.methods.iter().any(|m| m.eq_ignore_ascii_case(&request.method)) {
+        return error_response(405, error_path, "Method Not Allowed");
+    }
+    
+    // Soft-deleted files come back with `POST <path>?restore`
+    if route.trash && crate::handlers::is_restore_request(request) {
+        let file_path = match map_to_root(route, &request.path) {
+            Some(path) => path,
+            None => return error_response(403, error_path, "Forbidden"),
+        };
+        return crate::handlers::restore_from_trash(request, route, Path::new(&file_path))
+            .unwrap_or_else(|code| error_response(code, error_path, status_text(code)));
+    }
+    
+    // Handle redirect
+    if let Some(target) = &route.redirect {
+        let mut resp = HttpResponse::new(302, "Found");
+        resp.set_header("Location", target);
+        return resp;
+    }
+    
+    // PUT writes the body to exactly the requested path, DELETE removes it
+    let is_put = request.method.eq_ignore_ascii_case("PUT");
+    if (is_put || request.method.eq_ignore_ascii_case("DELETE")) && route.cgi.is_none() {
+        let file_path = match map_to_root(route, &request.path) {
+            Some(path) => path,
+            None => return error_response(403, error_path, "Forbidden"),
+        };
+        let result = if is_put {
+            crate::handlers::put_file(request, route, &file_path)
+        } else {
+            crate::handlers::delete_file(route, &file_path)
+        };
+        return result.unwrap_or_else(|code| error_response(code, error_path, status_text(code)));
+    }
+    
+    // Handle upload
+    if route.path.contains("/upload") && request.method.eq_ignore_ascii_case("POST") {
+        // Double check size limit for uploads and return proper error page
+        if request.body_len() > config.client_body_size_limit as u64 {
+            return error_response(413, error_path, "Payload Too Large");
+        }
+        let url_base = download_base(routes, route);
+        return crate::handlers::upload_file(request, route, &url_base, config.client_body_size_limit, error_path);
+    }
+    
+    // Handle CGI
+    if let Some(_cgi) = &route.cgi {
+        // Extract script name from URL path
+        // e.g., /cgi/test.py -> test.py
+        let after_route = request.path
+            .strip_prefix(&route.path)
+            .unwrap_or(&request.path)
+            .trim_start_matches('/');
+        
+        // Handle empty script name
+        if after_route.is_empty() {
+            return error_response(404, error_path, "Not Found");
+        }
+        
+        // SECURITY: Validate CGI script path
+        let script_path = match sanitize_path(&route.root, after_route) {
+            Some(safe_path) => safe_path.to_string_lossy().to_string(),
+            None => return error_response(403, error_path, "Forbidden"),
+        };
+        
+        let path_info = request.path.clone();
+        return crate::handlers::run_cgi(&script_path, &path_info, request);
+    }
+    
+    if !route.try_files.is_empty() {
+        return try_files(request, config, routes, route, error_path, redirects);
+    }
+    
+    // Build file path SAFELY
+    let file_path = match map_to_root(route, &request.path) {
+        Some(path) => path,
+        None => return error_response(403, error_path, "Forbidden"),
+    };
+    
+    let path_obj = Path::new(&file_path);
+    
+    // Serve file
+    if path_obj.is_file() {
+        return serve_static(request, config, route, &file_path, error_path);
+    }
+    
+    // Handle directory
+    if path_obj.is_dir() {
+        return serve_directory(request, config, route, &file_path, error_path);
     }
+    
+    error_response(404, error_path, "Not Found")
+}
+
+/// URL prefix where files stored by an upload route can be fetched: a
+/// GET route serving the same root (e.g. `/files` for `/upload`), or the
+/// upload route itself
+fn download_base(routes: &[RouteConfig], upload: &RouteConfig) -> String {
+    let root = upload.root.trim_end_matches('/');
+    routes
+        .iter()
+        .filter(|r| r.root.trim_end_matches('/') == root && r.cgi.is_none() && r.redirect.is_none())
+        .find(|r| r.methods.iter().any(|m| m == "GET"))
+        .map(|r| r.path.clone())
+        .unwrap_or_else(|| upload.path.clone())
+}
+
+/// Map a request URI onto the filesystem under the route's root.
+/// Returns None if the path tries to escape the root.
+fn map_to_root(route: &RouteConfig, uri: &str) -> Option<String> {
+    let relative = if route.path == "/" {
+        uri
+    } else {
+        uri.strip_prefix(&route.path).unwrap_or(uri)
+    };
+    let relative = relative.trim_start_matches('/');
+    
+    if relative.is_empty() {
+        return Some(route.root.clone());
+    }
+    
+    // SECURITY: Sanitize the path
+    sanitize_path(&route.root, relative).map(|p| p.to_string_lossy().to_string())
+}
+
+/// Evaluate `try_files`: the first candidate that exists is served (a
+/// trailing `/` asks for a directory), otherwise the last entry decides:
+/// `=code` returns that status, anything else is an internal redirect.
+fn try_files(
+    request: &HttpRequest,
+    config: &ServerConfig,
+    routes: &[RouteConfig],
+    route: &RouteConfig,
+    error_path: &str,
+    redirects: u8,
+) -> HttpResponse {
+    let (fallback, candidates) = match route.try_files.split_last() {
+        Some(split) => split,
+        None => return error_response(404, error_path, "Not Found"),
+    };
+    
+    for candidate in candidates {
+        let uri = candidate.replace("$uri", &request.path);
+        let want_dir = uri.ends_with('/');
+        
+        let path = match map_to_root(route, &uri) {
+            Some(path) => path,
+            None => continue,
+        };
+        let path_obj = Path::new(&path);
+        
+        if want_dir && path_obj.is_dir() {
+            return serve_directory(request, config, route, &path, error_path);
+        }
+        if !want_dir && path_obj.is_file() {
+            return serve_static(request, config, route, &path, error_path);
+        }
+    }
+    
+    if let Some(code) = fallback.strip_prefix('=') {
+        return match code.parse::<u16>() {
+            Ok(code) => error_response(code, error_path, status_text(code)),
+            Err(_) => error_response(500, error_path, "Internal Server Error"),
+        };
+    }
+    
+    // Internal redirect: re-route the rewritten URI without telling the client
+    if redirects >= MAX_INTERNAL_REDIRECTS {
+        eprintln!("[router] internal redirect loop while processing {}", request.path);
+        return error_response(500, error_path, "Internal Server Error");
+    }
+    
+    let target = fallback.replace("$uri", &request.path);
+    let mut rewritten = request.clone();
+    match target.split_once('?') {
+        Some((path, query)) => {
+            rewritten.path = path.to_string();
+            rewritten.query = query.to_string();
+        }
+        None => rewritten.path = target,
+    }
+    
+    if !is_path_safe(&rewritten.path) {
+        return error_response(403, error_path, "Forbidden");
+    }
+    
+    // Falling back into a route with other auth settings takes that
+    // route's credentials, as a direct request would
+    if find_route(&rewritten, routes).is_some_and(|target| target.auth != route.auth) {
+        match authenticate_request(&rewritten, config) {
+            Ok(Some(user)) => rewritten.remote_user = Some(user),
+            Ok(None) => {}
+            Err(challenge) => return *challenge,
+        }
+    }
+    
+    dispatch(&rewritten, config, routes, error_path, redirects + 1)
+}
+
+/// Serve a directory: its default file if present, else a listing when
+/// autoindex is on, else 403
+fn serve_directory(
+    request: &HttpRequest,
+    config: &ServerConfig,
+    route: &RouteConfig,
+    dir_path: &str,
+    error_path: &str,
+) -> HttpResponse {
+    // `?archive=tar|zip` downloads the whole tree instead
+    if route.autoindex
+        && route.autoindex_archive
+        && let Some(format) = crate::handlers::ArchiveFormat::from_query(&request.query)
+    {
+        return crate::handlers::archive_directory(dir_path, &route.root, format);
+    }
+    
+    if let Some(df) = &route.default_file {
+        let default_path = format!("{}/{}", dir_path.trim_end_matches('/'), df);
+        if Path::new(&default_path).is_file() {
+            return serve_static(request, config, route, &default_path, error_path);
+        }
+    }
+    
+    if route.autoindex {
+        let mut response = crate::handlers::list_directory(dir_path, request, route.autoindex_format, &config.mime_types);
+        apply_cache_policy(&mut response, route, None);
+        return response;
+    }
+    
+    error_response(403, error_path, "Forbidden")
+}
+
+/// Serve a regular file, preferring a precompressed sibling when the route
+/// has `gzip_static` on. Content-Type always comes from the original name.
+fn serve_static(
+    request: &HttpRequest,
+    config: &ServerConfig,
+    route: &RouteConfig,
+    file_path: &str,
+    error_path: &str,
+) -> HttpResponse {
+    let precompressed = if route.gzip_static {
+        let accept = request.header("Accept-Encoding").unwrap_or("");
+        crate::handlers::find_precompressed(file_path, accept)
+    } else {
+        None
+    };
+    
+    let (serve_path, encoding) = match &precompressed {
+        Some((path, coding)) => (path.as_str(), Some(*coding)),
+        None => (file_path, None),
+    };
+    
+    let content = match crate::handlers::serve_file(serve_path) {
+        Ok(content) => content,
+        Err(_) => return error_response(404, error_path, "Not Found"),
+    };
+    
+    let mut response = HttpResponse::ok();
+    response.set_header("Content-Type", &get_content_type(file_path, route, config));
+    
+    // ETag of the file actually sent, so each encoding gets its own
+    if let Ok(metadata) = std::fs::metadata(serve_path) {
+        response.set_header("ETag", &crate::handlers::file_etag(&metadata));
+    }
+    
+    if let Some(coding) = encoding {
+        response.set_header("Content-Encoding", coding);
+    }
+    if route.gzip_static {
+        add_vary(&mut response, "Accept-Encoding");
+    }
+    
+    response.set_body_bytes(content);
+    apply_cache_policy(&mut response, route, Some(file_path));
+    response
 }
 
 fn find_vhost<'a>(request: &HttpRequest, vhosts: &'a [VHost]) -> Option<&'a VHost> {
@@ -274,32 +600,107 @@ fn find_vhost<'a>(request: &HttpRequest, vhosts: &'a [VHost]) -> Option<&'a VHos
         .find(|v| Some(v.name.as_str()) == host)
 }
 
-fn error_response(code: u16, error_path: &str, message: &str) -> HttpResponse {
+pub fn error_response(code: u16, error_path: &str, message: &str) -> HttpResponse {
     let error_file = format!("{}/{}.html", error_path, code);
     
     match crate::handlers::serve_file(&error_file) {
         Ok(content) => {
             let mut response = HttpResponse::new(code, message);
             response.set_header("Content-Type", "text/html");
+            response.set_header("Cache-Control", "no-store");
             response.set_body_bytes(content);
             response
         }
         Err(_) => {
             let mut response = HttpResponse::new(code, message);
+            response.set_header("Cache-Control", "no-store");
             response.set_body(&format!("<h1>{} - {}</h1>", code, message));
             response
         }
     }
 }
 
-fn get_content_type(file_path: &str) -> &str {
-    if file_path.ends_with(".html") { "text/html" }
-    else if file_path.ends_with(".css") { "text/css" }
-    else if file_path.ends_with(".js") { "application/javascript" }
-    else if file_path.ends_with(".json") { "application/json" }
-    else if file_path.ends_with(".png") { "image/png" }
-    else if file_path.ends_with(".jpg") || file_path.ends_with(".jpeg") { "image/jpeg" }
-    else if file_path.ends_with(".gif") { "image/gif" }
-    else if file_path.ends_with(".txt") { "text/plain" }
-    else { "application/octet-stream" }
-}
\ No newline at end of file
+/// Add the route's Cache-Control / Expires headers for a file (or a
+/// directory listing when `file_path` is None)
+fn apply_cache_policy(response: &mut HttpResponse, route: &RouteConfig, file_path: Option<&str>) {
+    let cache_control = match_ext_rule(&route.cache_control, file_path);
+    if let Some(value) = cache_control {
+        response.set_header("Cache-Control", value);
+    }
+    
+    let max_age = match match_ext_rule(&route.expires, file_path) {
+        None | Some(Expires::Off) => return,
+        Some(Expires::Epoch) => None,
+        Some(Expires::Max) => Some(Expires::MAX_SECS),
+        Some(Expires::After(secs)) => Some(*secs),
+    };
+    let expires_at = match max_age {
+        // The parser caps `After`; a date past what SystemTime holds still
+        // falls back to `max` rather than panicking
+        Some(secs) => SystemTime::now()
+            .checked_add(Duration::from_secs(secs))
+            .or_else(|| SystemTime::now().checked_add(Duration::from_secs(Expires::MAX_SECS)))
+            .unwrap_or_else(SystemTime::now),
+        None => SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+    };
+    
+    response.set_header("Expires", &http_date(expires_at));
+    
+    // An explicit cache_control wins; otherwise derive it from expires
+    if cache_control.is_none() {
+        match max_age {
+            Some(secs) => response.set_header("Cache-Control", &format!("max-age={}", secs)),
+            None => response.set_header("Cache-Control", "no-cache"),
+        }
+    }
+}
+
+/// Resolve a file's Content-Type: route `mime_type` overrides, then the
+/// server MIME table, then the route's or server's `default_type`
+fn get_content_type(file_path: &str, route: &RouteConfig, config: &ServerConfig) -> String {
+    let mime = match_ext_rule(&route.mime_types, Some(file_path))
+        .map(|m| m.as_str())
+        .or_else(|| config.mime_types.lookup(file_path))
+        .or(route.default_type.as_deref())
+        .unwrap_or(&config.default_type);
+    
+    with_charset(mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config_string;
+    use std::fs;
+
+    fn put(config: &ServerConfig, path: &str, header: &str) -> u16 {
+        let raw = format!("PUT {} HTTP/1.1\r\nHost: localhost\r\n{}\r\nContent-Length: 11\r\n\r\nhello world", path, header);
+        let request = HttpRequest::parse(raw.as_bytes()).unwrap();
+        route_request(&request, config).status_code
+    }
+
+    #[test]
+    fn body_digest_is_checked_before_storing() {
+        let root = std::env::temp_dir().join(format!("localhost-digest-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let config = parse_config_string(&format!(
+            "route /store {{\n    methods = PUT\n    root = {}\n}}\n",
+            root.display()
+        ))
+        .unwrap();
+
+        assert_eq!(put(&config, "/store/md5.txt", "Content-MD5: XrY7u+Ae7tCTyyK7j1rNww=="), 201);
+        assert_eq!(
+            put(&config, "/store/sha.txt", "Digest: SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="),
+            201
+        );
+        assert_eq!(fs::read(root.join("md5.txt")).unwrap(), b"hello world");
+
+        // A mismatch is refused and nothing is written
+        assert_eq!(put(&config, "/store/bad.txt", "Content-MD5: 1B2M2Y8AsgTpgAmY7PhCfg=="), 400);
+        assert_eq!(put(&config, "/store/bad.txt", "Digest: SHA-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="), 400);
+        assert!(!root.join("bad.txt").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,14 +1,18 @@
 #![cfg(unix)]
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::net::TcpListener;
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
 
-use crate::config::ServerConfig;
-use crate::http::{HttpRequest, HttpResponse};
+use crate::config::{ServerConfig, SessionBackend, SessionMode};
+use crate::handlers::{clear_session_cookie, SessionStore};
+use crate::http::{HttpRequest, HttpResponse, RequestSession, SessionUpdate};
 
 use super::connection::{ClientConnection, ConnState, ConnectionError};
 use super::epoll_wrapper::{Epoll, Interest};
-use super::{create_listener, route_request};
+use super::spool::{BodySpool, SpoolError};
+use super::{authenticate_request, create_listener, error_response, route_request, session_mode, upload_target};
 
 /// Maximum idle time before closing connection (no activity at all)
 const IDLE_TIMEOUT_SECS: u64 = 30;
@@ -19,16 +23,37 @@ const REQUEST_TIMEOUT_SECS: u64 = 30;
 /// How often to check for timeouts (milliseconds)
 const TIMEOUT_CHECK_MS: i32 = 1000;
 
+/// How often expired trash and sessions are purged (seconds)
+const SWEEP_INTERVAL_SECS: u64 = 60;
+
 /// Maximum requests per keep-alive connection
 const MAX_REQUESTS_PER_CONN: u32 = 100;
 
 pub struct Server {
     config: ServerConfig,
+    sessions: RefCell<SessionStore>,
 }
 
 impl Server {
     pub fn new(config: ServerConfig) -> Self {
-        Self { config }
+        let sessions = match &config.session.store {
+            SessionBackend::Memory => SessionStore::new(),
+            SessionBackend::Cookie => SessionStore::stateless(),
+            SessionBackend::File(dir) => match SessionStore::open(dir) {
+                Ok(store) => {
+                    println!("[session] loaded {} session(s) from {}", store.len(), dir);
+                    store
+                }
+                Err(e) => {
+                    eprintln!("[session] cannot open store {}: {}; keeping sessions in memory", dir, e);
+                    SessionStore::new()
+                }
+            },
+        };
+        Self {
+            config,
+            sessions: RefCell::new(sessions),
+        }
     }
     
     pub fn run(&self) -> std::io::Result<()> {
@@ -51,10 +76,7 @@ impl Server {
         }
         
         if listeners.is_empty() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No listeners created"
-            ));
+            return Err(std::io::Error::other("No listeners created"));
         }
         
         // Create epoll instance
@@ -70,6 +92,9 @@ impl Server {
         
         println!("[server] ready to accept connections");
         
+        let mut last_sweep = Instant::now();
+        self.sweep_trash();
+        
         // Main event loop
         loop {
             // Wait for events (with timeout for cleanup)
@@ -100,6 +125,7 @@ impl Server {
                     
                     // Handle readable event
                     if event.can_read && !should_close {
+                        let had_interim = client.interim_pending();
                         match self.handle_read(client) {
                             Ok(request_ready) => {
                                 if request_ready {
@@ -112,10 +138,20 @@ impl Server {
                                 should_close = true;
                             }
                         }
+                        // A 100 Continue was queued: wait for the socket to take it
+                        if !had_interim && client.interim_pending() {
+                            needs_interest_update = true;
+                        }
                     }
                     
-                    // Handle writable event
-                    if event.can_write && !should_close {
+                    // Handle writable event: while the request is still being
+                    // read, only an interim 100 Continue goes out
+                    if event.can_write && !should_close && client.state == ConnState::Reading {
+                        match client.flush_interim() {
+                            Ok(done) => needs_interest_update |= done,
+                            Err(_) => should_close = true,
+                        }
+                    } else if event.can_write && !should_close {
                         match self.handle_write(client) {
                             Ok(write_done) => {
                                 if write_done {
@@ -138,7 +174,7 @@ impl Server {
                     
                     // Update epoll interest if state changed
                     if needs_interest_update && !should_close {
-                        let interest = self.get_interest_for_state(client.state);
+                        let interest = self.get_interest(client);
                         if let Err(e) = poller.modify(fd, interest) {
                             eprintln!("[server] epoll modify failed: {}", e);
                             should_close = true;
@@ -154,6 +190,31 @@ impl Server {
             
             // Clean up closed/timed-out connections (including REQUEST timeouts)
             self.cleanup_connections(&poller, &mut clients);
+            
+            if last_sweep.elapsed().as_secs() >= SWEEP_INTERVAL_SECS {
+                self.sweep_trash();
+                self.sweep_sessions();
+                last_sweep = Instant::now();
+            }
+            
+            // Persist whatever this round changed before waiting again
+            self.sessions.borrow_mut().flush();
+        }
+    }
+    
+    /// Forget sessions that have expired
+    fn sweep_sessions(&self) {
+        let expired = self.sessions.borrow_mut().sweep();
+        if expired > 0 {
+            println!("[session] expired {} session(s)", expired);
+        }
+    }
+    
+    /// Purge expired trash on every route that keeps one
+    fn sweep_trash(&self) {
+        let vhost_routes = self.config.vhosts.iter().flat_map(|v| &v.routes);
+        for route in self.config.routes.iter().chain(vhost_routes) {
+            crate::handlers::sweep_trash(route);
         }
     }
     
@@ -206,6 +267,76 @@ impl Server {
             return Ok(false);
         }
         
+        if client.body_spool.is_none()
+            && let Some((head, body_start)) = HttpRequest::parse_head(&client.read_buffer)
+        {
+            // A body framed by both headers could be split differently by
+            // a proxy in front of us (request smuggling); RFC 9112 §6.1
+            // lets the server refuse it
+            if head.has_conflicting_framing() {
+                client.early_response = Some(error_response(400, &self.config.error_path, "Bad Request"));
+                return Ok(true);
+            }
+            
+            // Upload bodies go to disk as they arrive
+            if let Some(target) = upload_target(&head, &self.config) {
+                // Nothing is stored for a client that can't log in
+                if let Err(challenge) = authenticate_request(&head, &self.config) {
+                    client.early_response = Some(*challenge);
+                    return Ok(true);
+                }
+                
+                if head.content_length().is_some_and(|len| len > target.limit) {
+                    // Refuse before reading any of the body
+                    client.early_response = Some(error_response(413, &target.error_path, "Payload Too Large"));
+                    return Ok(true);
+                }
+                
+                match BodySpool::start(head, &target.dir, target.limit, &target.error_path) {
+                    Some(Ok(spool)) => {
+                        let expects_continue = spool.expects_continue();
+                        client.read_buffer.drain(..body_start);
+                        client.body_spool = Some(spool);
+                        if expects_continue && client.read_buffer.is_empty() {
+                            client.queue_interim(b"HTTP/1.1 100 Continue\r\n\r\n");
+                        }
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("[server] cannot spool upload into {}: {}", target.dir, e);
+                        client.early_response = Some(error_response(500, &target.error_path, "Internal Server Error"));
+                        return Ok(true);
+                    }
+                    // No body framing: handled as a regular request
+                    None => {}
+                }
+            }
+        }
+        
+        if let Some(spool) = client.body_spool.as_mut() {
+            let result = spool.feed(&client.read_buffer);
+            let error_path = spool.error_path.clone();
+            match result {
+                Ok(consumed) => {
+                    client.read_buffer.drain(..consumed);
+                    return Ok(spool.is_complete());
+                }
+                Err(e) => {
+                    let (code, message) = match e {
+                        SpoolError::TooLarge => (413, "Payload Too Large"),
+                        SpoolError::Malformed => (400, "Bad Request"),
+                        SpoolError::Io(err) => {
+                            eprintln!("[server] upload write failed: {}", err);
+                            (500, "Internal Server Error")
+                        }
+                    };
+                    // Dropping the spool deletes the partial file
+                    client.body_spool = None;
+                    client.early_response = Some(error_response(code, &error_path, message));
+                    return Ok(true);
+                }
+            }
+        }
+        
         // Check if we have a complete request
         Ok(HttpRequest::parse(&client.read_buffer).is_some())
     }
@@ -218,8 +349,31 @@ impl Server {
     
     /// Process request and queue response for writing
     fn process_and_queue_response(&self, client: &mut ClientConnection) {
+        // Rejected before the body was read: the rest of it is still in
+        // flight, so the connection cannot be reused
+        if let Some(mut response) = client.early_response.take() {
+            response.set_header("Connection", "close");
+            client.keep_alive = false;
+            client.queue_response(response.to_bytes());
+            return;
+        }
+        
+        let spooled = match client.body_spool.take() {
+            Some(spool) => match spool.finish() {
+                Ok(req) => Some(req),
+                Err(e) => {
+                    eprintln!("[server] upload flush failed: {}", e);
+                    let resp = HttpResponse::internal_error();
+                    client.queue_response(resp.to_bytes());
+                    client.keep_alive = false;
+                    return;
+                }
+            },
+            None => None,
+        };
+        
         // Parse request (we know it's complete)
-        let request = match HttpRequest::parse(&client.read_buffer) {
+        let mut request = match spooled.or_else(|| HttpRequest::parse(&client.read_buffer)) {
             Some(req) => req,
             None => {
                 // Shouldn't happen, but handle gracefully
@@ -238,16 +392,27 @@ impl Server {
             .map(|v| !v.eq_ignore_ascii_case("close"))
             .unwrap_or(true);
         
-        // Route and generate response
-        let mut response = route_request(&request, &self.config);
+        let mode = session_mode(&request, &self.config);
+        let new_session = self.resume_session(&mut request, mode);
         
-        // Handle session cookie
-        let cookie = request.headers.get("Cookie").map(|s| s.as_str());
-        if crate::handlers::get_session_id(cookie).is_none() {
-            let sid = crate::handlers::create_session_id();
-            response.set_header("Set-Cookie", &format!("SID={}; Path=/; HttpOnly", sid));
+        // Route and generate response, once the client has shown the
+        // credentials the route asks for
+        let mut response = match authenticate_request(&request, &self.config) {
+            Ok(user) => {
+                request.remote_user = user;
+                route_request(&request, &self.config)
+            }
+            Err(challenge) => *challenge,
+        };
+        
+        // The handler moves a spooled body into place; anything left over
+        // is an upload that was refused
+        if let Some(body_file) = &request.body_file {
+            let _ = std::fs::remove_file(&body_file.path);
         }
         
+        self.finish_session(&request, mode, new_session, &mut response);
+        
         // Set connection header
         if client.keep_alive {
             response.set_header("Connection", "keep-alive");
@@ -258,12 +423,99 @@ impl Server {
         
         // Queue response for writing
         let response_bytes = response.to_bytes();
-        client.queue_response(response_bytes);
+        match response.stream.take() {
+            Some(stream) => client.queue_streaming_response(response_bytes, stream),
+            None => client.queue_response(response_bytes),
+        }
+    }
+    
+    /// Resume the client's session, or with `sessions = always` start one,
+    /// and hand it to the request for handlers to see. An unknown or
+    /// expired ID gets a fresh session, never the one asked for, so clients
+    /// can't choose their own IDs. Returns whether the session is new.
+    fn resume_session(&self, request: &mut HttpRequest, mode: SessionMode) -> bool {
+        if mode == SessionMode::Off {
+            return false;
+        }
+        let config = &self.config.session;
+        let mut sessions = self.sessions.borrow_mut();
+        
+        if let Some(session) = sessions.resume(request.header("Cookie"), config) {
+            request.session = Some(RequestSession { id: session.id.clone(), data: session.data.clone() });
+            return false;
+        }
+        if mode == SessionMode::Lazy {
+            return false;
+        }
+        
+        match sessions.create(config) {
+            Ok(session) => {
+                request.session = Some(RequestSession { id: session.id.clone(), data: HashMap::new() });
+                true
+            }
+            Err(e) => {
+                eprintln!("[session] cannot create session: {}", e);
+                false
+            }
+        }
+    }
+    
+    /// Apply the session changes the handler asked for, starting a session
+    /// for them if the request had none, and give the client the cookie it
+    /// needs now (or expire a destroyed session's)
+    fn finish_session(&self, request: &HttpRequest, mode: SessionMode, mut new_session: bool, response: &mut HttpResponse) {
+        let updates = std::mem::take(&mut response.session_updates);
+        if mode == SessionMode::Off {
+            if !updates.is_empty() {
+                eprintln!("[session] ignoring session changes for {}: sessions are off", request.path);
+            }
+            return;
+        }
+        
+        let config = &self.config.session;
+        let mut sessions = self.sessions.borrow_mut();
+        
+        let id = match &request.session {
+            Some(session) => session.id.clone(),
+            // Nothing to store means no reason to start a session
+            None if !updates.iter().any(|u| matches!(u, SessionUpdate::Set(..))) => return,
+            None => match sessions.create(config) {
+                Ok(session) => {
+                    new_session = true;
+                    session.id.clone()
+                }
+                Err(e) => {
+                    eprintln!("[session] cannot create session: {}", e);
+                    return;
+                }
+            },
+        };
+        let mut destroyed = false;
+        
+        for update in updates {
+            match update {
+                SessionUpdate::Set(key, value) => sessions.set_value(&id, &key, &value),
+                SessionUpdate::Remove(key) => sessions.remove_value(&id, &key),
+                SessionUpdate::Destroy => {
+                    sessions.remove(&id);
+                    destroyed = true;
+                }
+            }
+        }
+        
+        if destroyed {
+            if !new_session {
+                response.set_header("Set-Cookie", &clear_session_cookie(config));
+            }
+        } else if let Some(cookie) = sessions.outgoing_cookie(&id, new_session, config) {
+            response.set_header("Set-Cookie", &cookie);
+        }
     }
     
     /// Get epoll interest flags based on connection state
-    fn get_interest_for_state(&self, state: ConnState) -> Interest {
-        match state {
+    fn get_interest(&self, client: &ClientConnection) -> Interest {
+        match client.state {
+            ConnState::Reading if client.interim_pending() => Interest::both(),
             ConnState::Reading => Interest::readable(),
             ConnState::Writing => Interest::writable(),
             ConnState::Closing => Interest::readable(), // Will be removed anyway
@@ -0,0 +1,147 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::handlers::create_upload_temp;
+use crate::crypto::{Md5, Sha256};
+use crate::http::{BodyFile, ChunkedDecoder, HttpRequest};
+
+/// Buffer size for writes to the temp file
+const WRITE_BUFFER: usize = 64 * 1024;
+
+/// Receives an upload body straight into a temp file in the target
+/// directory while it is read, instead of buffering it in memory.
+/// The temp file is removed on drop unless it was handed off by `finish`.
+pub struct BodySpool {
+    request: HttpRequest,
+    path: PathBuf,
+    file: BufWriter<File>,
+    hasher: Sha256,
+    md5: Md5,
+    framing: Framing,
+    received: u64,
+    limit: u64,
+    started: Instant,
+    pub error_path: String,
+    handed_off: bool,
+}
+
+enum Framing {
+    /// Content-Length body with this many bytes still to come
+    Length(u64),
+    Chunked(ChunkedDecoder),
+}
+
+#[derive(Debug)]
+pub enum SpoolError {
+    /// Body grew past the configured limit
+    TooLarge,
+    /// Broken chunked framing
+    Malformed,
+    Io(io::Error),
+}
+
+impl BodySpool {
+    /// Start spooling the body of `request` (parsed head only) into `dir`.
+    /// Returns None if the request carries no body framing.
+    pub fn start(request: HttpRequest, dir: &str, limit: u64, error_path: &str) -> Option<io::Result<Self>> {
+        let framing = if request.is_chunked() {
+            Framing::Chunked(ChunkedDecoder::new())
+        } else {
+            Framing::Length(request.content_length()?)
+        };
+
+        Some(create_upload_temp(dir).map(|(path, file)| Self {
+            request,
+            path,
+            file: BufWriter::with_capacity(WRITE_BUFFER, file),
+            hasher: Sha256::new(),
+            md5: Md5::new(),
+            framing,
+            received: 0,
+            limit,
+            started: Instant::now(),
+            error_path: error_path.to_string(),
+            handed_off: false,
+        }))
+    }
+
+    /// Write as much of `input` as belongs to the body. Returns how many
+    /// bytes were consumed; bytes after the end of the body are left alone.
+    pub fn feed(&mut self, input: &[u8]) -> Result<usize, SpoolError> {
+        match &mut self.framing {
+            Framing::Length(remaining) => {
+                let take = (*remaining).min(input.len() as u64) as usize;
+                *remaining -= take as u64;
+                self.write(&input[..take])?;
+                Ok(take)
+            }
+            Framing::Chunked(decoder) => {
+                let mut decoded = Vec::new();
+                let consumed = decoder.feed(input, &mut decoded).map_err(|_| SpoolError::Malformed)?;
+                self.write(&decoded)?;
+                Ok(consumed)
+            }
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), SpoolError> {
+        self.received += data.len() as u64;
+        if self.received > self.limit {
+            return Err(SpoolError::TooLarge);
+        }
+        self.hasher.update(data);
+        self.md5.update(data);
+        self.file.write_all(data).map_err(SpoolError::Io)
+    }
+
+    /// Body bytes received so far
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Time since the body started arriving
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether the client waits for `100 Continue` before sending the body
+    pub fn expects_continue(&self) -> bool {
+        self.request
+            .header("Expect")
+            .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
+    }
+
+    /// Whether the whole body has been received
+    pub fn is_complete(&self) -> bool {
+        match &self.framing {
+            Framing::Length(remaining) => *remaining == 0,
+            Framing::Chunked(decoder) => decoder.is_done(),
+        }
+    }
+
+    /// Flush the temp file and hand it over as the request's `body_file`.
+    /// From here on the caller owns the file on disk.
+    pub fn finish(mut self) -> io::Result<HttpRequest> {
+        self.file.flush()?;
+        self.handed_off = true;
+
+        let mut request = self.request.clone();
+        request.body_file = Some(BodyFile {
+            path: self.path.clone(),
+            len: self.received,
+            sha256: self.hasher.clone().finish(),
+            md5: self.md5.clone().finish(),
+        });
+        Ok(request)
+    }
+}
+
+impl Drop for BodySpool {
+    fn drop(&mut self) {
+        if !self.handed_off {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}